@@ -1,26 +1,43 @@
 // Copyright 2025 Google LLC
 // SPDX-License-Identifier: MIT
 
+mod dnsmasq;
 mod kea;
 mod linux;
+mod modem;
+mod nut;
+mod process;
+mod speedtest;
+mod ubus;
 mod unbound;
+mod wanip;
 
-use crate::metric;
-use anyhow::Result;
-use log::debug;
-use std::sync;
+use crate::{config, metric};
+use anyhow::{Result, anyhow};
+use log::{debug, error, info, warn};
+use std::{collections, error, fmt, fs, io, num, path, sync, time};
 
 const NAMESPACE: &str = "homerouter";
+// process metrics follow the convention of official client libraries
+const PROCESS_NAMESPACE: &str = "";
 const SUBSYS_CPU: &str = "cpu";
 const SUBSYS_MEMORY: &str = "memory";
 const SUBSYS_FILESYSTEM: &str = "filesystem";
 const SUBSYS_THERMAL: &str = "thermal";
 const SUBSYS_NETWORK: &str = "network";
+const SUBSYS_POWER: &str = "power";
+const SUBSYS_PROCESS: &str = "process";
+const SUBSYS_EXPORTER: &str = "exporter";
+// system-wide metrics are not grouped under a subsystem
+const SUBSYS_SYSTEM: &str = "";
 
 struct CpuMetrics {
     idle: metric::Info<1>,
+    usage: metric::Info<1>,
 
     current_frequency: metric::Info<1>,
+    clock_synchronized: metric::Info<0>,
+    clock_offset: metric::Info<0>,
 }
 
 struct MemoryMetrics {
@@ -31,6 +48,10 @@ struct MemoryMetrics {
 
     swap_in: metric::Info<0>,
     swap_out: metric::Info<0>,
+    swap_in_rate: metric::Info<0>,
+    swap_out_rate: metric::Info<0>,
+
+    zram_compression: metric::Info<1>,
 }
 
 struct FilesystemMetrics {
@@ -38,30 +59,130 @@ struct FilesystemMetrics {
     available: metric::Info<2>,
     read: metric::Info<2>,
     write: metric::Info<2>,
+    readonly: metric::Info<2>,
+    flash_wear: metric::Info<1>,
+    flash_life_remaining: metric::Info<1>,
 }
 
 struct ThermalMetrics {
     temperature: metric::Info<1>,
+    throttle_events: metric::Info<1>,
+    fan_pwm: metric::Info<2>,
+    disk_temperature: metric::Info<1>,
 }
 
 struct NetworkMetrics {
     link_speed: metric::Info<1>,
 
+    link_ring_rx: metric::Info<1>,
+    link_ring_tx: metric::Info<1>,
+    link_pause_rx: metric::Info<1>,
+    link_pause_tx: metric::Info<1>,
+    link_eee_active: metric::Info<1>,
+    link_eee_enabled: metric::Info<1>,
+    link_hw_timestamping: metric::Info<1>,
+    link_stat: metric::Info<2>,
     link_up: metric::Info<1>,
-    link_operstate: metric::Info<1>,
+    link_operstate: metric::Info<2>,
+    link_mismatch: metric::Info<1>,
     link_rx: metric::Info<1>,
     link_tx: metric::Info<1>,
+    link_multicast: metric::Info<1>,
+    link_collisions: metric::Info<1>,
+    link_up_since: metric::Info<1>,
+    link_rx_rate: metric::Info<1>,
+    link_tx_rate: metric::Info<1>,
+
+    ppp_info: metric::Info<2>,
+    ppp_session_uptime: metric::Info<1>,
 
     route_default: metric::Info<1>,
+    default_route_present: metric::Info<0>,
+    route_count: metric::Info<2>,
+
+    tcp_out_segments: metric::Info<0>,
+    tcp_retransmitted_segments: metric::Info<0>,
+    tcp_retransmit_ratio: metric::Info<0>,
+
+    ip6_in_receives: metric::Info<0>,
+    ip6_in_discards: metric::Info<0>,
+    ip6_reasm_fails: metric::Info<0>,
+    icmp6_in_errors: metric::Info<0>,
+    icmp6_in_router_advertisements: metric::Info<0>,
+
+    tcp_connections: metric::Info<1>,
+    listen_port_info: metric::Info<3>,
+    ephemeral_ports: metric::Info<0>,
+    ephemeral_ports_used: metric::Info<1>,
+
+    softnet_processed: metric::Info<1>,
+    softnet_dropped: metric::Info<1>,
+    softnet_time_squeezed: metric::Info<1>,
 
     nft_set_counter: metric::Info<4>,
+    nft_chains: metric::Info<2>,
+    nft_rules: metric::Info<3>,
 
+    dhcp_up: metric::Info<0>,
     dhcp_received: metric::Info<0>,
     dhcp_sent: metric::Info<0>,
     dhcp_addr_fail: metric::Info<0>,
+    dhcp_active_leases: metric::Info<0>,
+    dhcp_leases: metric::Info<1>,
+    dhcp_lease_change: metric::Info<0>,
+    dhcp6_release_received: metric::Info<0>,
+    dhcp6_pd_events: metric::Info<1>,
+    dhcp6_assigned_prefixes: metric::Info<1>,
+
+    dns_up: metric::Info<1>,
+    dns_query: metric::Info<1>,
+    dns_timeout: metric::Info<1>,
+    dns_query_type: metric::Info<2>,
+    dns_distinct_clients: metric::Info<0>,
+    dns_forward_info: metric::Info<3>,
+    dns_cache_hits: metric::Info<0>,
+    dns_cache_misses: metric::Info<0>,
+
+    wan_ip_info: metric::Info<1>,
+    wan_ip_change: metric::Info<0>,
+    wan_download: metric::Info<0>,
+
+    dsl_snr: metric::Info<1>,
+    dsl_attenuation: metric::Info<1>,
+    dsl_sync_rate: metric::Info<1>,
+
+    ubus_up: metric::Info<0>,
+    netifd_interface_up: metric::Info<1>,
+    netifd_interface_uptime: metric::Info<1>,
+    wireless_clients: metric::Info<1>,
+}
+
+struct PowerMetrics {
+    ups_up: metric::Info<1>,
+    battery_charge: metric::Info<1>,
+    input_voltage: metric::Info<1>,
+    on_battery: metric::Info<1>,
+}
+
+struct ProcessMetrics {
+    cpu: metric::Info<0>,
+    resident_memory: metric::Info<0>,
+    open_fds: metric::Info<0>,
+    start_time: metric::Info<0>,
+}
+
+struct ExporterMetrics {
+    errors: metric::Info<1>,
+    collector_ready: metric::Info<1>,
+    collector_runs: metric::Info<1>,
+    last_scrape: metric::Info<0>,
+}
 
-    dns_query: metric::Info<0>,
-    dns_timeout: metric::Info<0>,
+struct SystemMetrics {
+    host_info: metric::Info<2>,
+    os_info: metric::Info<3>,
+    hardware_info: metric::Info<2>,
+    sysctl: metric::Info<1>,
 }
 
 struct Metrics {
@@ -70,6 +191,10 @@ struct Metrics {
     fs: FilesystemMetrics,
     thermal: ThermalMetrics,
     net: NetworkMetrics,
+    power: PowerMetrics,
+    process: ProcessMetrics,
+    system: SystemMetrics,
+    exporter: ExporterMetrics,
 }
 
 impl Metrics {
@@ -83,6 +208,14 @@ impl Metrics {
                 ty: metric::Type::Counter,
                 label_keys: ["cpu"],
             },
+            usage: metric::Info {
+                subsys: SUBSYS_CPU,
+                name: "usage",
+                help: "CPU busy time ratio since the last scrape",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: ["cpu"],
+            },
 
             current_frequency: metric::Info {
                 subsys: SUBSYS_CPU,
@@ -92,6 +225,22 @@ impl Metrics {
                 ty: metric::Type::Gauge,
                 label_keys: ["cpu"],
             },
+            clock_synchronized: metric::Info {
+                subsys: SUBSYS_CPU,
+                name: "clock_synchronized",
+                help: "Whether the kernel considers the system clock synchronized",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            clock_offset: metric::Info {
+                subsys: SUBSYS_CPU,
+                name: "clock_offset",
+                help: "Remaining system clock offset being corrected by the kernel",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
         };
 
         let mem = MemoryMetrics {
@@ -143,6 +292,31 @@ impl Metrics {
                 ty: metric::Type::Counter,
                 label_keys: [],
             },
+            swap_in_rate: metric::Info {
+                subsys: SUBSYS_MEMORY,
+                name: "swap_in",
+                help: "Swap in rate since the last scrape",
+                unit: metric::Unit::BytesPerSecond,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            swap_out_rate: metric::Info {
+                subsys: SUBSYS_MEMORY,
+                name: "swap_out",
+                help: "Swap out rate since the last scrape",
+                unit: metric::Unit::BytesPerSecond,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+
+            zram_compression: metric::Info {
+                subsys: SUBSYS_MEMORY,
+                name: "zram_compression",
+                help: "Zram original to compressed data size ratio",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
         };
 
         let fs = FilesystemMetrics {
@@ -178,6 +352,30 @@ impl Metrics {
                 ty: metric::Type::Counter,
                 label_keys: ["device", "mountpoint"],
             },
+            readonly: metric::Info {
+                subsys: SUBSYS_FILESYSTEM,
+                name: "readonly",
+                help: "Whether the filesystem is mounted read-only",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device", "mountpoint"],
+            },
+            flash_wear: metric::Info {
+                subsys: SUBSYS_FILESYSTEM,
+                name: "flash_wear",
+                help: "Estimated fraction of the flash device life time used",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            flash_life_remaining: metric::Info {
+                subsys: SUBSYS_FILESYSTEM,
+                name: "flash_life_remaining",
+                help: "Estimated fraction of the flash device life time remaining",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
         };
 
         let thermal = ThermalMetrics {
@@ -189,6 +387,30 @@ impl Metrics {
                 ty: metric::Type::Gauge,
                 label_keys: ["device"],
             },
+            throttle_events: metric::Info {
+                subsys: SUBSYS_THERMAL,
+                name: "throttle_events",
+                help: "Total times the temperature rose past the passive trip point",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["device"],
+            },
+            fan_pwm: metric::Info {
+                subsys: SUBSYS_THERMAL,
+                name: "fan_pwm",
+                help: "Fan PWM duty cycle",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: ["chip", "sensor"],
+            },
+            disk_temperature: metric::Info {
+                subsys: SUBSYS_THERMAL,
+                name: "disk_temperature",
+                help: "Disk drive temperature",
+                unit: metric::Unit::Celsius,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
         };
 
         let net = NetworkMetrics {
@@ -201,6 +423,70 @@ impl Metrics {
                 label_keys: ["device"],
             },
 
+            link_ring_rx: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_ring_rx",
+                help: "Link rx ring size",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_ring_tx: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_ring_tx",
+                help: "Link tx ring size",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_pause_rx: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_pause_rx",
+                help: "Link rx pause frame use",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_pause_tx: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_pause_tx",
+                help: "Link tx pause frame use",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_eee_active: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_eee_active",
+                help: "Link Energy-Efficient Ethernet active state",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_eee_enabled: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_eee_enabled",
+                help: "Link Energy-Efficient Ethernet enabled state",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_hw_timestamping: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_hw_timestamping",
+                help: "Whether a link supports hardware tx and rx timestamping",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_stat: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_stat",
+                help: "Link standard hardware statistics",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["device", "stat"],
+            },
             link_up: metric::Info {
                 subsys: SUBSYS_NETWORK,
                 name: "link_up",
@@ -215,6 +501,14 @@ impl Metrics {
                 help: "Link operational state",
                 unit: metric::Unit::None,
                 ty: metric::Type::Gauge,
+                label_keys: ["device", "state"],
+            },
+            link_mismatch: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_mismatch",
+                help: "Whether a link is administratively up but operationally not",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
                 label_keys: ["device"],
             },
             link_rx: metric::Info {
@@ -233,15 +527,212 @@ impl Metrics {
                 ty: metric::Type::Counter,
                 label_keys: ["device"],
             },
+            link_multicast: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_multicast",
+                help: "Total received multicast packets",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["device"],
+            },
+            link_collisions: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_collisions",
+                help: "Total tx collisions",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["device"],
+            },
+            link_up_since: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_up_since",
+                help: "Wall-clock time the link was last seen transitioning to up",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_rx_rate: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_rx",
+                help: "Network device receive rate since the last scrape",
+                unit: metric::Unit::BitsPerSecond,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+            link_tx_rate: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "link_tx",
+                help: "Network device transmit rate since the last scrape",
+                unit: metric::Unit::BitsPerSecond,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+
+            ppp_info: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ppp",
+                help: "PPP session info",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["device", "mtu"],
+            },
+            ppp_session_uptime: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ppp_session_uptime",
+                help: "PPP session uptime as observed by the exporter",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
 
             route_default: metric::Info {
                 subsys: SUBSYS_NETWORK,
                 name: "route_default",
                 help: "Default route",
-                unit: metric::Unit::Info,
-                ty: metric::Type::Gauge,
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
                 label_keys: ["gateway"],
             },
+            default_route_present: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "default_route_present",
+                help: "Whether any default route exists",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            route_count: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "route_count",
+                help: "Number of routes",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["table", "family"],
+            },
+
+            tcp_out_segments: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "tcp_out_segments",
+                help: "TCP total sent segments, excluding retransmissions",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            tcp_retransmitted_segments: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "tcp_retransmitted_segments",
+                help: "TCP total retransmitted segments",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            tcp_retransmit_ratio: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "tcp_retransmit",
+                help: "TCP retransmitted to sent segment ratio since the last scrape",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+
+            ip6_in_receives: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ip6_in_receives",
+                help: "IPv6 total received datagrams",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            ip6_in_discards: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ip6_in_discards",
+                help: "IPv6 total discarded input datagrams",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            ip6_reasm_fails: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ip6_reasm_fails",
+                help: "IPv6 total reassembly failures",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            icmp6_in_errors: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "icmp6_in_errors",
+                help: "ICMPv6 total received erroneous messages",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            icmp6_in_router_advertisements: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "icmp6_in_router_advertisements",
+                help: "ICMPv6 total received router advertisements",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+
+            tcp_connections: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "tcp_connections",
+                help: "TCP socket count by state",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["state"],
+            },
+            listen_port_info: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "listen_port",
+                help: "Listening TCP or bound UDP port",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["proto", "port", "address"],
+            },
+            ephemeral_ports: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ephemeral_ports",
+                help: "Size of the local port range for outbound connections",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            ephemeral_ports_used: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ephemeral_ports_used",
+                help: "Distinct local ports of sockets within the local port range",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["proto"],
+            },
+
+            softnet_processed: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "softnet_processed",
+                help: "Total packets processed by the softirq backlog",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["cpu"],
+            },
+            softnet_dropped: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "softnet_dropped",
+                help: "Total packets dropped because the softirq backlog was full",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["cpu"],
+            },
+            softnet_time_squeezed: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "softnet_time_squeezed",
+                help: "Total times the softirq ran out of budget with work remaining",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["cpu"],
+            },
 
             nft_set_counter: metric::Info {
                 subsys: SUBSYS_NETWORK,
@@ -249,9 +740,33 @@ impl Metrics {
                 help: "Nftables set counter",
                 unit: metric::Unit::Bytes,
                 ty: metric::Type::Counter,
-                label_keys: ["family", "table", "set", "key"],
+                label_keys: ["family", "table", "set", "addr"],
+            },
+            nft_chains: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "nft_chains",
+                help: "Nftables chain count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["family", "table"],
+            },
+            nft_rules: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "nft_rules",
+                help: "Nftables rule count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["family", "table", "chain"],
             },
 
+            dhcp_up: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dhcp_up",
+                help: "Whether the last connection to the DHCP server succeeded",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
             dhcp_received: metric::Info {
                 subsys: SUBSYS_NETWORK,
                 name: "dhcp_received",
@@ -276,23 +791,342 @@ impl Metrics {
                 ty: metric::Type::Counter,
                 label_keys: [],
             },
-
-            dns_query: metric::Info {
+            dhcp_active_leases: metric::Info {
                 subsys: SUBSYS_NETWORK,
-                name: "dns_query",
-                help: "DHCP total query count",
+                name: "dhcp_active_leases",
+                help: "DHCP active lease count",
                 unit: metric::Unit::None,
-                ty: metric::Type::Counter,
+                ty: metric::Type::Gauge,
                 label_keys: [],
             },
-            dns_timeout: metric::Info {
+            dhcp_leases: metric::Info {
                 subsys: SUBSYS_NETWORK,
-                name: "dns_timeout",
-                help: "DHCP total query timeout",
+                name: "dhcp_leases",
+                help: "DHCP lease count by state",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["state"],
+            },
+            dhcp_lease_change: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dhcp_lease_change",
+                help: "DHCP total active lease count change count",
                 unit: metric::Unit::None,
                 ty: metric::Type::Counter,
                 label_keys: [],
             },
+            dhcp6_release_received: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dhcp6_release_received",
+                help: "DHCPv6 total release packet received",
+                unit: metric::Unit::Packets,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            dhcp6_pd_events: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dhcp6_pd_events",
+                help: "DHCPv6 total delegated prefix events (e.g., lease_reuses)",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["event"],
+            },
+            dhcp6_assigned_prefixes: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dhcp6_assigned_prefixes",
+                help: "DHCPv6 delegated prefix count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["subnet"],
+            },
+
+            dns_up: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_up",
+                help: "Whether the last connection to the DNS server succeeded",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["instance"],
+            },
+            dns_query: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_query",
+                help: "DHCP total query count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["instance"],
+            },
+            dns_timeout: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_timeout",
+                help: "DHCP total query timeout",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["instance"],
+            },
+            dns_query_type: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_query_type",
+                help: "DNS total query count by query type",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["instance", "type"],
+            },
+            dns_distinct_clients: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_distinct_clients",
+                help: "Distinct client addresses in the DNS query log within the window",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            dns_forward_info: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_forward",
+                help: "DNS forward zone and upstream server",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["instance", "zone", "server"],
+            },
+            dns_cache_hits: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_cache_hits",
+                help: "DNS total cache hit count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            dns_cache_misses: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dns_cache_misses",
+                help: "DNS total cache miss count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+
+            wan_ip_info: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "wan_ip",
+                help: "External IP address as seen by a STUN server",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["ip"],
+            },
+            wan_ip_change: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "wan_ip_change",
+                help: "External IP address total change count",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            wan_download: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "wan_download",
+                help: "Download throughput of the last speed test",
+                unit: metric::Unit::BitsPerSecond,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+
+            dsl_snr: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dsl_snr",
+                help: "DSL line signal-to-noise ratio margin",
+                unit: metric::Unit::Decibels,
+                ty: metric::Type::Gauge,
+                label_keys: ["direction"],
+            },
+            dsl_attenuation: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dsl_attenuation",
+                help: "DSL line attenuation",
+                unit: metric::Unit::Decibels,
+                ty: metric::Type::Gauge,
+                label_keys: ["direction"],
+            },
+            dsl_sync_rate: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "dsl_sync_rate",
+                help: "DSL line sync rate",
+                unit: metric::Unit::BitsPerSecond,
+                ty: metric::Type::Gauge,
+                label_keys: ["direction"],
+            },
+
+            ubus_up: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "ubus_up",
+                help: "Whether the last connection to ubus succeeded",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            netifd_interface_up: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "netifd_interface_up",
+                help: "Whether the OpenWrt logical interface is up",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["interface"],
+            },
+            netifd_interface_uptime: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "netifd_interface_uptime",
+                help: "OpenWrt logical interface uptime",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Gauge,
+                label_keys: ["interface"],
+            },
+            wireless_clients: metric::Info {
+                subsys: SUBSYS_NETWORK,
+                name: "wireless_clients",
+                help: "Wireless stations associated with the hostapd interface",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["device"],
+            },
+        };
+
+        let power = PowerMetrics {
+            ups_up: metric::Info {
+                subsys: SUBSYS_POWER,
+                name: "ups_up",
+                help: "Whether the last query to upsd for the UPS succeeded",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["ups"],
+            },
+            battery_charge: metric::Info {
+                subsys: SUBSYS_POWER,
+                name: "battery_charge",
+                help: "UPS battery charge",
+                unit: metric::Unit::Ratio,
+                ty: metric::Type::Gauge,
+                label_keys: ["ups"],
+            },
+            input_voltage: metric::Info {
+                subsys: SUBSYS_POWER,
+                name: "input_voltage",
+                help: "UPS input voltage",
+                unit: metric::Unit::Volts,
+                ty: metric::Type::Gauge,
+                label_keys: ["ups"],
+            },
+            on_battery: metric::Info {
+                subsys: SUBSYS_POWER,
+                name: "on_battery",
+                help: "Whether the UPS is running on battery",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["ups"],
+            },
+        };
+
+        let process = ProcessMetrics {
+            cpu: metric::Info {
+                subsys: SUBSYS_PROCESS,
+                name: "cpu",
+                help: "Total user and system CPU time spent",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Counter,
+                label_keys: [],
+            },
+            resident_memory: metric::Info {
+                subsys: SUBSYS_PROCESS,
+                name: "resident_memory",
+                help: "Resident memory size",
+                unit: metric::Unit::Bytes,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            open_fds: metric::Info {
+                subsys: SUBSYS_PROCESS,
+                name: "open_fds",
+                help: "Number of open file descriptors",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+            start_time: metric::Info {
+                subsys: SUBSYS_PROCESS,
+                name: "start_time",
+                help: "Start time of the process since unix epoch",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
+        };
+
+        let system = SystemMetrics {
+            host_info: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "host",
+                help: "Host info",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["hostname", "kernel"],
+            },
+            os_info: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "os",
+                help: "Operating system info",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["kernel_release", "kernel_version", "machine"],
+            },
+            hardware_info: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "hardware",
+                help: "Board model and vendor from the device tree or DMI",
+                unit: metric::Unit::None,
+                ty: metric::Type::Info,
+                label_keys: ["model", "vendor"],
+            },
+            sysctl: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "sysctl",
+                help: "Kernel parameter value",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["name"],
+            },
+        };
+
+        let exporter = ExporterMetrics {
+            errors: metric::Info {
+                subsys: SUBSYS_EXPORTER,
+                name: "errors",
+                help: "Total collector errors",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["collector"],
+            },
+            collector_ready: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "collector_ready",
+                help: "Whether a background collector has collected successfully, or 0 for a disabled collector",
+                unit: metric::Unit::None,
+                ty: metric::Type::Gauge,
+                label_keys: ["collector"],
+            },
+            collector_runs: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "collector_runs",
+                help: "Total collector runs, successful or not",
+                unit: metric::Unit::None,
+                ty: metric::Type::Counter,
+                label_keys: ["collector"],
+            },
+            last_scrape: metric::Info {
+                subsys: SUBSYS_SYSTEM,
+                name: "last_scrape_timestamp",
+                help: "Wall-clock time of the previous scrape",
+                unit: metric::Unit::Seconds,
+                ty: metric::Type::Gauge,
+                label_keys: [],
+            },
         };
 
         Metrics {
@@ -301,50 +1135,868 @@ impl Metrics {
             fs,
             thermal,
             net,
+            power,
+            process,
+            system,
+            exporter,
         }
     }
+
+    // metrics without labels are omitted
+    fn label_keys(&self) -> collections::BTreeSet<&'static str> {
+        let keys: [&[&'static str]; 73] = [
+            &self.cpu.idle.label_keys,
+            &self.cpu.usage.label_keys,
+            &self.cpu.current_frequency.label_keys,
+            &self.mem.zram_compression.label_keys,
+            &self.fs.size.label_keys,
+            &self.fs.available.label_keys,
+            &self.fs.read.label_keys,
+            &self.fs.write.label_keys,
+            &self.fs.readonly.label_keys,
+            &self.fs.flash_wear.label_keys,
+            &self.fs.flash_life_remaining.label_keys,
+            &self.thermal.temperature.label_keys,
+            &self.thermal.throttle_events.label_keys,
+            &self.thermal.fan_pwm.label_keys,
+            &self.thermal.disk_temperature.label_keys,
+            &self.net.link_speed.label_keys,
+            &self.net.link_ring_rx.label_keys,
+            &self.net.link_ring_tx.label_keys,
+            &self.net.link_pause_rx.label_keys,
+            &self.net.link_pause_tx.label_keys,
+            &self.net.link_eee_active.label_keys,
+            &self.net.link_eee_enabled.label_keys,
+            &self.net.link_hw_timestamping.label_keys,
+            &self.net.link_stat.label_keys,
+            &self.net.link_up.label_keys,
+            &self.net.link_operstate.label_keys,
+            &self.net.link_mismatch.label_keys,
+            &self.net.link_rx.label_keys,
+            &self.net.link_tx.label_keys,
+            &self.net.link_multicast.label_keys,
+            &self.net.link_collisions.label_keys,
+            &self.net.link_up_since.label_keys,
+            &self.net.link_rx_rate.label_keys,
+            &self.net.link_tx_rate.label_keys,
+            &self.net.ppp_info.label_keys,
+            &self.net.ppp_session_uptime.label_keys,
+            &self.net.route_default.label_keys,
+            &self.net.route_count.label_keys,
+            &self.net.tcp_connections.label_keys,
+            &self.net.listen_port_info.label_keys,
+            &self.net.ephemeral_ports_used.label_keys,
+            &self.net.softnet_processed.label_keys,
+            &self.net.softnet_dropped.label_keys,
+            &self.net.softnet_time_squeezed.label_keys,
+            &self.net.nft_set_counter.label_keys,
+            &self.net.nft_chains.label_keys,
+            &self.net.nft_rules.label_keys,
+            &self.net.dhcp_leases.label_keys,
+            &self.net.dhcp6_pd_events.label_keys,
+            &self.net.dhcp6_assigned_prefixes.label_keys,
+            &self.net.dns_up.label_keys,
+            &self.net.dns_query.label_keys,
+            &self.net.dns_timeout.label_keys,
+            &self.net.dns_query_type.label_keys,
+            &self.net.dns_forward_info.label_keys,
+            &self.net.wan_ip_info.label_keys,
+            &self.net.dsl_snr.label_keys,
+            &self.net.dsl_attenuation.label_keys,
+            &self.net.dsl_sync_rate.label_keys,
+            &self.net.netifd_interface_up.label_keys,
+            &self.net.netifd_interface_uptime.label_keys,
+            &self.net.wireless_clients.label_keys,
+            &self.power.ups_up.label_keys,
+            &self.power.battery_charge.label_keys,
+            &self.power.input_voltage.label_keys,
+            &self.power.on_battery.label_keys,
+            &self.system.host_info.label_keys,
+            &self.system.os_info.label_keys,
+            &self.system.hardware_info.label_keys,
+            &self.system.sysctl.label_keys,
+            &self.exporter.errors.label_keys,
+            &self.exporter.collector_ready.label_keys,
+            &self.exporter.collector_runs.label_keys,
+        ];
+
+        keys.into_iter().flatten().copied().collect()
+    }
+}
+
+// collector errors, classified such that callers can pick a log level with a
+// match instead of downcasting
+enum CollectorError {
+    PermissionDenied(anyhow::Error),
+    NotFound(anyhow::Error),
+    Netlink(anyhow::Error),
+    Parse(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+impl CollectorError {
+    // netlink errors are not classified by From because the neli error types
+    // are generic
+    fn netlink(err: anyhow::Error) -> Self {
+        match Self::from(err) {
+            CollectorError::Other(err) => CollectorError::Netlink(err),
+            err => err,
+        }
+    }
+
+    fn inner(&self) -> &anyhow::Error {
+        match self {
+            CollectorError::PermissionDenied(err)
+            | CollectorError::NotFound(err)
+            | CollectorError::Netlink(err)
+            | CollectorError::Parse(err)
+            | CollectorError::Other(err) => err,
+        }
+    }
+}
+
+impl From<anyhow::Error> for CollectorError {
+    fn from(err: anyhow::Error) -> Self {
+        let io_kind = err
+            .chain()
+            .find_map(|err| err.downcast_ref::<io::Error>())
+            .map(io::Error::kind);
+        match io_kind {
+            Some(io::ErrorKind::PermissionDenied) => return CollectorError::PermissionDenied(err),
+            Some(io::ErrorKind::NotFound) => return CollectorError::NotFound(err),
+            _ => (),
+        }
+
+        let is_parse = err.chain().any(|err| {
+            err.is::<num::ParseIntError>()
+                || err.is::<num::ParseFloatError>()
+                || err.is::<serde_json::Error>()
+        });
+        if is_parse {
+            CollectorError::Parse(err)
+        } else {
+            CollectorError::Other(err)
+        }
+    }
+}
+
+impl fmt::Debug for CollectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.inner(), f)
+    }
+}
+
+impl fmt::Display for CollectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.inner(), f)
+    }
+}
+
+impl error::Error for CollectorError {}
+
+// how long a synchronous scrape waits for the background collectors
+const SYNCHRONOUS_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+// collectors that cannot work without procfs or sysfs
+const PROCFS_COLLECTORS: [&str; 15] = [
+    "host",
+    "cpu",
+    "mem_info",
+    "mem_vm",
+    "fs",
+    "sysctl",
+    "net_snmp",
+    "net_snmp6",
+    "net_tcp",
+    "net_listen",
+    "net_ephemeral_ports",
+    "net_softnet",
+    "process_stat",
+    "process_status",
+    "process_fds",
+];
+const SYSFS_COLLECTORS: [&str; 5] = ["hardware", "mem_zram", "thermal", "hwmon", "fs_flash"];
+
+// identical errors from a collector are logged at most once per interval
+const ERROR_LOG_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+struct ErrorLog {
+    level: log::Level,
+    msg: String,
+    time: time::Instant,
+    repeated: u64,
+}
+
+impl ErrorLog {
+    fn flush(&self, collector: &str) {
+        if self.repeated > 0 {
+            log::log!(
+                self.level,
+                "last {collector} error repeated {} times",
+                self.repeated
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+struct ErrorCounter {
+    // every collector reports each run here, successful or not
+    runs: sync::Mutex<collections::BTreeMap<&'static str, u64>>,
+    counts: sync::Mutex<collections::BTreeMap<&'static str, u64>>,
+    logs: sync::Mutex<collections::HashMap<&'static str, ErrorLog>>,
+}
+
+impl ErrorCounter {
+    fn update<T, E>(&self, collector: &'static str, res: &Result<T, E>) {
+        *self.runs.lock().unwrap().entry(collector).or_default() += 1;
+
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(collector).or_default();
+        if res.is_err() {
+            *count += 1;
+        } else if let Some(last) = self.logs.lock().unwrap().remove(collector) {
+            last.flush(collector);
+        }
+    }
+
+    fn log(&self, collector: &'static str, level: log::Level, msg: String) {
+        let mut logs = self.logs.lock().unwrap();
+        let now = time::Instant::now();
+
+        if let Some(last) = logs.get_mut(collector) {
+            if last.msg == msg && now.duration_since(last.time) < ERROR_LOG_INTERVAL {
+                last.repeated += 1;
+                return;
+            }
+
+            last.flush(collector);
+        }
+
+        log::log!(level, "{msg}");
+        logs.insert(
+            collector,
+            ErrorLog {
+                level,
+                msg,
+                time: now,
+                repeated: 0,
+            },
+        );
+    }
+
+    // runs a collector if the filter selects it, and counts and logs its error
+    fn run(
+        &self,
+        filter: &Filter,
+        collector: &'static str,
+        what: &str,
+        f: impl FnOnce() -> Result<(), CollectorError>,
+    ) {
+        self.run_with_level(filter, collector, what, |_| log::Level::Error, f);
+    }
+
+    fn run_with_level(
+        &self,
+        filter: &Filter,
+        collector: &'static str,
+        what: &str,
+        level: impl FnOnce(&CollectorError) -> log::Level,
+        f: impl FnOnce() -> Result<(), CollectorError>,
+    ) {
+        if !filter.matches(collector) {
+            return;
+        }
+
+        let res = f();
+        self.update(collector, &res);
+        if let Err(err) = res {
+            self.log(
+                collector,
+                level(&err),
+                format!("failed to collect {what}: {err:?}"),
+            );
+        }
+    }
+
+    fn collect(&self, metrics: &Metrics, enc: &mut metric::Encoder) {
+        let mut menc = enc.with_info(&metrics.exporter.errors, None);
+        for (collector, count) in self.counts.lock().unwrap().iter() {
+            menc.write(&[collector], count);
+        }
+
+        let mut menc = enc.with_info(&metrics.exporter.collector_runs, None);
+        for (collector, count) in self.runs.lock().unwrap().iter() {
+            menc.write(&[collector], count);
+        }
+    }
+}
+
+/// Selects collectors by name or by name prefix (e.g., `net` selects `net_route`).
+#[derive(Default)]
+pub struct Filter {
+    collectors: Vec<String>,
+    // never matched, regardless of collectors
+    disabled: Vec<&'static str>,
+}
+
+impl Filter {
+    pub fn new(collectors: Vec<String>) -> Self {
+        Filter {
+            collectors,
+            disabled: Vec::new(),
+        }
+    }
+
+    fn with_disabled(&self, disabled: &[&'static str]) -> Self {
+        Filter {
+            collectors: self.collectors.clone(),
+            disabled: disabled.to_vec(),
+        }
+    }
+
+    fn matches(&self, collector: &str) -> bool {
+        if self.disabled.contains(&collector) {
+            return false;
+        }
+        if self.collectors.is_empty() {
+            return true;
+        }
+
+        self.collectors.iter().any(|name| {
+            collector
+                .strip_prefix(name.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('_'))
+        })
+    }
 }
 
 pub struct Collector {
-    lin: linux::Linux,
-    kea: sync::Arc<kea::Kea>,
+    lin: sync::RwLock<linux::Linux>,
+    process: process::Process,
+    kea: Option<sync::Arc<kea::Kea>>,
+    dnsmasq: Option<sync::Arc<dnsmasq::Dnsmasq>>,
     unbound: sync::Arc<unbound::Unbound>,
+    wanip: Option<sync::Arc<wanip::WanIp>>,
+    speedtest: Option<sync::Arc<speedtest::Speedtest>>,
+    modem: Option<sync::Arc<modem::Modem>>,
+    ubus: Option<sync::Arc<ubus::Ubus>>,
+    nut: Option<sync::Arc<nut::Nut>>,
 
+    errors: sync::Arc<ErrorCounter>,
     metrics: Metrics,
+    const_labels: Vec<(&'static str, &'static str)>,
+    subsys_prefixes: Vec<(&'static str, &'static str)>,
+    float_precision: Option<usize>,
+    timestamps: metric::Timestamps,
+    counter_offsets: Option<metric::CounterOffsets>,
+    name_filter: metric::NameFilter,
+    synchronous: bool,
+    // collectors whose pseudo filesystems are not mounted
+    disabled: Vec<&'static str>,
+    last_scrape: sync::Mutex<Option<time::SystemTime>>,
+}
+
+// const labels are added to every series, which must not have repeated label
+// names
+fn check_const_labels(
+    label_keys: &collections::BTreeSet<&str>,
+    const_labels: &[(&str, &str)],
+) -> Result<()> {
+    for (idx, (key, _)) in const_labels.iter().enumerate() {
+        if const_labels[..idx].iter().any(|(other, _)| other == key) {
+            return Err(anyhow!("duplicate const label {key}"));
+        }
+        if label_keys.contains(key) {
+            return Err(anyhow!("const label {key} conflicts with a metric label"));
+        }
+    }
+
+    Ok(())
+}
+
+// an unmounted mount point is usually a missing or empty directory
+fn is_mounted(path: &path::Path) -> bool {
+    fs::read_dir(path).is_ok_and(|mut dir| dir.next().is_some())
 }
 
 impl Collector {
     pub fn new() -> Result<Self> {
         debug!("creating collector");
 
-        let lin = linux::Linux::new()?;
-        let kea = kea::Kea::new()?;
-        let unbound = unbound::Unbound::new();
+        let errors = sync::Arc::new(ErrorCounter::default());
+
+        let lin = linux::Linux::new(errors.clone())?;
+        let process = process::Process::new(errors.clone());
+        let (kea, dnsmasq) = match config::get().dhcp_backend {
+            config::DhcpBackend::Kea => (Some(kea::Kea::new(errors.clone())?), None),
+            config::DhcpBackend::Dnsmasq => (None, Some(dnsmasq::Dnsmasq::new(errors.clone()))),
+        };
+        let unbound = unbound::Unbound::new(errors.clone());
+        // disabled by default because they make outbound requests
+        let wanip = config::get()
+            .wanip_stun_server
+            .as_deref()
+            .map(|server| wanip::WanIp::new(errors.clone(), server));
+        let speedtest = config::get()
+            .speedtest_url
+            .as_ref()
+            .map(|url| speedtest::Speedtest::new(errors.clone(), url));
+        let modem = Some(&config::get().modem_command)
+            .filter(|command| !command.is_empty())
+            .map(|command| modem::Modem::new(errors.clone(), command));
+        // only present on OpenWrt
+        let ubus = config::get()
+            .ubus_socket
+            .as_deref()
+            .map(|path| ubus::Ubus::new(errors.clone(), path));
+        let nut = Some(&config::get().nut_upses)
+            .filter(|upses| !upses.is_empty())
+            .map(|upses| nut::Nut::new(errors.clone(), upses));
+
+        let mut disabled = Vec::new();
+        let config = config::get();
+        for (path, collectors) in [
+            (&config.procfs_path, &PROCFS_COLLECTORS[..]),
+            (&config.sysfs_path, &SYSFS_COLLECTORS[..]),
+        ] {
+            if !is_mounted(path) {
+                error!(
+                    "{path:?} is not mounted; disabling {}",
+                    collectors.join(", ")
+                );
+                disabled.extend_from_slice(collectors);
+            }
+        }
 
         let metrics = Metrics::new();
+        let const_labels = config::get()
+            .const_labels
+            .iter()
+            .map(|(key, val)| (key.as_str(), val.as_str()))
+            .collect::<Vec<_>>();
+        check_const_labels(&metrics.label_keys(), &const_labels)?;
+        let subsys_prefixes = config::get()
+            .subsys_prefixes
+            .iter()
+            .map(|(subsys, prefix)| (subsys.as_str(), prefix.as_str()))
+            .collect();
 
         Ok(Collector {
-            lin,
+            lin: sync::RwLock::new(lin),
+            process,
             kea,
+            dnsmasq,
             unbound,
+            wanip,
+            speedtest,
+            modem,
+            ubus,
+            nut,
+            errors,
             metrics,
+            const_labels,
+            subsys_prefixes,
+            float_precision: config::get().float_precision,
+            synchronous: config::get().synchronous,
+            disabled,
+            timestamps: config::get().timestamps,
+            counter_offsets: Self::load_counter_offsets(),
+            name_filter: metric::NameFilter {
+                include: config::get().metric_include.clone(),
+                exclude: config::get().metric_exclude.clone(),
+            },
+            last_scrape: Default::default(),
         })
     }
 
+    // the linux and process collectors read the fixture trees and the
+    // optional collectors are disabled
+    #[cfg(test)]
+    fn with_fixtures(procfs: &str, sysfs: &str) -> Self {
+        let errors = sync::Arc::new(ErrorCounter::default());
+
+        Collector {
+            lin: sync::RwLock::new(linux::Linux::with_fixtures(procfs, sysfs)),
+            process: process::Process::with_fixtures(errors.clone(), procfs),
+            kea: None,
+            dnsmasq: None,
+            unbound: unbound::Unbound::without_instances(errors.clone()),
+            wanip: None,
+            speedtest: None,
+            modem: None,
+            ubus: None,
+            nut: None,
+            errors,
+            metrics: Metrics::new(),
+            const_labels: Vec::new(),
+            subsys_prefixes: Vec::new(),
+            float_precision: None,
+            timestamps: metric::Timestamps::Never,
+            counter_offsets: None,
+            name_filter: Default::default(),
+            synchronous: false,
+            disabled: Vec::new(),
+            last_scrape: Default::default(),
+        }
+    }
+
+    fn load_counter_offsets() -> Option<metric::CounterOffsets> {
+        let config = config::get();
+        if !config.monotonic_counters {
+            return None;
+        }
+
+        let offsets = config.state_file.as_ref().and_then(|path| {
+            // a missing state file is expected on the first run
+            if !path.exists() {
+                return None;
+            }
+
+            metric::CounterOffsets::load(path)
+                .inspect_err(|err| warn!("failed to load counter offsets: {err:?}"))
+                .ok()
+        });
+
+        Some(offsets.unwrap_or_default())
+    }
+
+    pub fn save_state(&self) {
+        let (Some(offsets), Some(path)) = (&self.counter_offsets, &config::get().state_file) else {
+            return;
+        };
+
+        debug!("saving counter offsets to {path:?}");
+        if let Err(err) = offsets.save(path) {
+            error!("failed to save counter offsets: {err:?}");
+        }
+    }
+
     pub fn content_type() -> &'static str {
         "text/plain; version=0.0.4"
     }
 
-    pub fn collect(&self) -> String {
+    fn collect_os(&self, enc: &mut metric::Encoder) -> Result<(), CollectorError> {
+        let [release, version, machine] = crate::libc::uname()?;
+
+        enc.with_info(&self.metrics.system.os_info, None)
+            .write_info(&[&release, &version, &machine]);
+
+        Ok(())
+    }
+
+    // re-open netlink sockets, e.g., after the network namespace is recreated
+    pub fn reload(&self) -> Result<()> {
+        info!("reloading collector");
+
+        self.lin.write().unwrap().reopen()
+    }
+
+    pub fn collect(&self, filter: &Filter) -> String {
         debug!("collecting metrics");
 
+        let orig_filter = filter;
+        let filter = &filter.with_disabled(&self.disabled);
+
         let mut buf = String::with_capacity(4096);
-        let mut enc = metric::Encoder::new(&mut buf, NAMESPACE);
 
-        self.lin.collect(&self.metrics, &mut enc);
-        self.kea.collect(&self.metrics, &mut enc);
-        self.unbound.collect(&self.metrics, &mut enc);
+        // request the refreshes first such that they overlap with the
+        // collectors below
+        let seqs = self.synchronous.then(|| {
+            let kea = self.kea.as_ref().filter(|_| filter.matches("kea"));
+            let dnsmasq = self.dnsmasq.as_ref().filter(|_| filter.matches("dnsmasq"));
+            let unbound = Some(&self.unbound).filter(|_| filter.matches("unbound"));
+            let ubus = self.ubus.as_ref().filter(|_| filter.matches("ubus"));
+            let nut = self.nut.as_ref().filter(|_| filter.matches("nut"));
+            (
+                kea.map(|kea| (kea, kea.request_refresh())),
+                dnsmasq.map(|dnsmasq| (dnsmasq, dnsmasq.request_refresh())),
+                unbound.map(|unbound| (unbound, unbound.request_refresh())),
+                ubus.map(|ubus| (ubus, ubus.request_refresh())),
+                nut.map(|nut| (nut, nut.request_refresh())),
+            )
+        });
+
+        if let Some(offsets) = &self.counter_offsets {
+            offsets.prune();
+        }
+
+        let mut enc = metric::Encoder::new(
+            &mut buf,
+            PROCESS_NAMESPACE,
+            &self.const_labels,
+            &self.subsys_prefixes,
+            self.float_precision,
+            self.timestamps,
+            self.counter_offsets.as_ref(),
+            &self.name_filter,
+        );
+        self.process.collect(&self.metrics, filter, &mut enc);
+
+        let mut enc = metric::Encoder::new(
+            &mut buf,
+            NAMESPACE,
+            &self.const_labels,
+            &self.subsys_prefixes,
+            self.float_precision,
+            self.timestamps,
+            self.counter_offsets.as_ref(),
+            &self.name_filter,
+        );
+
+        self.errors
+            .run(filter, "os", "os info", || self.collect_os(&mut enc));
+
+        self.lin
+            .read()
+            .unwrap()
+            .collect(&self.metrics, filter, &mut enc);
+
+        if let Some((kea, dnsmasq, unbound, ubus, nut)) = seqs {
+            let wait = async {
+                tokio::join!(
+                    async {
+                        if let Some((kea, seq)) = kea {
+                            kea.wait_refreshed(seq).await;
+                        }
+                    },
+                    async {
+                        if let Some((dnsmasq, seq)) = dnsmasq {
+                            dnsmasq.wait_refreshed(seq).await;
+                        }
+                    },
+                    async {
+                        if let Some((unbound, seq)) = unbound {
+                            unbound.wait_refreshed(seq).await;
+                        }
+                    },
+                    async {
+                        if let Some((ubus, seq)) = ubus {
+                            ubus.wait_refreshed(seq).await;
+                        }
+                    },
+                    async {
+                        if let Some((nut, seq)) = nut {
+                            nut.wait_refreshed(seq).await;
+                        }
+                    },
+                )
+            };
+
+            // we are on a blocking thread
+            let res = tokio::runtime::Handle::current()
+                .block_on(tokio::time::timeout(SYNCHRONOUS_TIMEOUT, wait));
+            if res.is_err() {
+                warn!("timed out waiting for background collectors");
+            }
+        }
+
+        if let Some(kea) = &self.kea {
+            kea.collect(&self.metrics, filter, &mut enc);
+        }
+        if let Some(dnsmasq) = &self.dnsmasq {
+            dnsmasq.collect(&self.metrics, filter, &mut enc);
+        }
+        self.unbound.collect(&self.metrics, filter, &mut enc);
+        if let Some(wanip) = &self.wanip {
+            wanip.collect(&self.metrics, filter, &mut enc);
+        }
+        if let Some(speedtest) = &self.speedtest {
+            speedtest.collect(&self.metrics, filter, &mut enc);
+        }
+        if let Some(modem) = &self.modem {
+            modem.collect(&self.metrics, filter, &mut enc);
+        }
+        if let Some(ubus) = &self.ubus {
+            ubus.collect(&self.metrics, filter, &mut enc);
+        }
+        if let Some(nut) = &self.nut {
+            nut.collect(&self.metrics, filter, &mut enc);
+        }
+
+        // background collectors have nothing to report until the first success
+        let mut menc = enc.with_info(&self.metrics.exporter.collector_ready, None);
+        if let Some(kea) = self.kea.as_ref().filter(|_| filter.matches("kea")) {
+            menc.write(&["kea"], kea.is_ready() as u64);
+        }
+        if let Some(dnsmasq) = self.dnsmasq.as_ref().filter(|_| filter.matches("dnsmasq")) {
+            menc.write(&["dnsmasq"], dnsmasq.is_ready() as u64);
+        }
+        if filter.matches("unbound") {
+            menc.write(&["unbound"], self.unbound.is_ready() as u64);
+        }
+        if let Some(wanip) = self.wanip.as_ref().filter(|_| filter.matches("wanip")) {
+            menc.write(&["wanip"], wanip.is_ready() as u64);
+        }
+        if let Some(speedtest) = self
+            .speedtest
+            .as_ref()
+            .filter(|_| filter.matches("speedtest"))
+        {
+            menc.write(&["speedtest"], speedtest.is_ready() as u64);
+        }
+        if let Some(modem) = self.modem.as_ref().filter(|_| filter.matches("modem")) {
+            menc.write(&["modem"], modem.is_ready() as u64);
+        }
+        if let Some(ubus) = self.ubus.as_ref().filter(|_| filter.matches("ubus")) {
+            menc.write(&["ubus"], ubus.is_ready() as u64);
+        }
+        if let Some(nut) = self.nut.as_ref().filter(|_| filter.matches("nut")) {
+            menc.write(&["nut"], nut.is_ready() as u64);
+        }
+        for collector in &self.disabled {
+            if orig_filter.matches(collector) {
+                menc.write(&[collector], 0);
+            }
+        }
+
+        self.errors.collect(&self.metrics, &mut enc);
+
+        // this scrape is reported by the next one
+        let now = time::SystemTime::now();
+        if let Some(last) = self.last_scrape.lock().unwrap().replace(now) {
+            let secs = last
+                .duration_since(time::UNIX_EPOCH)
+                .map_or(0.0, |dur| dur.as_secs_f64());
+            enc.write(&self.metrics.exporter.last_scrape, secs, None);
+        }
 
         buf
     }
 }
+
+#[cfg(test)]
+fn fixture_path(name: &str) -> &'static path::Path {
+    let path = path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    Box::leak(path.into_boxed_path())
+}
+
+// renders what the collector encodes, for tests
+#[cfg(test)]
+fn render(f: impl FnOnce(&Metrics, &mut metric::Encoder)) -> String {
+    let metrics = Metrics::new();
+    let name_filter = Default::default();
+    let mut text = String::new();
+    let mut enc = metric::Encoder::new(
+        &mut text,
+        "test",
+        &[],
+        &[],
+        None,
+        metric::Timestamps::Never,
+        None,
+        &name_filter,
+    );
+    f(&metrics, &mut enc);
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter;
+
+    #[test]
+    fn const_label_conflicts() {
+        let label_keys = Metrics::new().label_keys();
+        assert!(label_keys.contains("device"));
+        assert!(label_keys.contains("instance"));
+
+        assert!(check_const_labels(&label_keys, &[("site", "home"), ("region", "eu")]).is_ok());
+        // a repeated const label
+        assert!(check_const_labels(&label_keys, &[("site", "home"), ("site", "work")]).is_err());
+        // the dns metrics have their own instance label
+        assert!(check_const_labels(&label_keys, &[("instance", "gw")]).is_err());
+        for key in ["device", "cpu", "collector", "ups"] {
+            assert!(check_const_labels(&label_keys, &[(key, "x")]).is_err());
+        }
+    }
+    #[test]
+    fn instance_label_conflicts() {
+        let label_keys = Metrics::new().label_keys();
+        assert!(check_const_labels(&label_keys, &[("router", "gw")]).is_ok());
+        assert!(check_const_labels(&label_keys, &[("router", "gw"), ("router", "x")]).is_err());
+
+        // a metric with its own router label
+        let label_keys = collections::BTreeSet::from(["device", "router"]);
+        assert!(check_const_labels(&label_keys, &[("router", "gw")]).is_err());
+    }
+
+    // parses a scrape with a real parser, which skips what it does not
+    // understand
+    fn parse(text: &str) -> Vec<prometheus_parse::Sample> {
+        let lines = text.lines().map(|line| Ok(line.to_string()));
+        let scrape = prometheus_parse::Scrape::parse(lines).unwrap();
+
+        let sample_lines: Vec<_> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(scrape.samples.len(), sample_lines.len());
+        for (line, sample) in iter::zip(sample_lines, &scrape.samples) {
+            // repeated label names collapse when parsed
+            assert_eq!(sample.labels.len(), line.matches("=\"").count(), "{line}");
+        }
+
+        scrape.samples
+    }
+
+    #[test]
+    fn scrape_fixtures() {
+        let mut collector = Collector::with_fixtures("procfs", "sysfs");
+        collector.const_labels = vec![("site", "home")];
+        let samples = parse(&collector.collect(&Filter::default()));
+
+        assert!(
+            samples
+                .iter()
+                .all(|sample| sample.labels.get("site") == Some("home"))
+        );
+
+        let value = |name: &str, labels: &[(&str, &str)]| {
+            samples
+                .iter()
+                .find(|sample| {
+                    sample.metric == name
+                        && labels
+                            .iter()
+                            .all(|(key, val)| sample.labels.get(key) == Some(val))
+                })
+                .map(|sample| sample.value.clone())
+        };
+
+        assert_eq!(
+            value("homerouter_cpu_idle_seconds_total", &[("cpu", "cpu1")]),
+            Some(prometheus_parse::Value::Counter(689.96))
+        );
+        assert_eq!(
+            value("homerouter_memory_size_bytes", &[]),
+            Some(prometheus_parse::Value::Gauge(8234541056.0))
+        );
+        assert_eq!(
+            value(
+                "homerouter_thermal_temperature_celsius",
+                &[("device", "x86_pkg_temp")]
+            ),
+            Some(prometheus_parse::Value::Gauge(45.0))
+        );
+        assert!(
+            value(
+                "homerouter_filesystem_read_bytes_total",
+                &[("device", "/dev/sda"), ("mountpoint", "/")]
+            )
+            .is_some()
+        );
+        assert_eq!(
+            value("process_open_fds", &[]),
+            Some(prometheus_parse::Value::Gauge(3.0))
+        );
+        assert_eq!(
+            value(
+                "homerouter_exporter_errors_total",
+                &[("collector", "process_stat")]
+            ),
+            Some(prometheus_parse::Value::Counter(0.0))
+        );
+    }
+}
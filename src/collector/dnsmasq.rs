@@ -0,0 +1,300 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, Result, anyhow};
+use std::{net, path, sync, time};
+
+const DNS_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+const DNS_TYPE_TXT: u16 = 16;
+const DNS_CLASS_CH: u16 = 3;
+
+struct Stats {
+    timestamp: time::SystemTime,
+    active_leases: u64,
+}
+
+struct CacheStats {
+    timestamp: time::SystemTime,
+    hits: u64,
+    misses: u64,
+}
+
+fn build_chaos_query(id: u16, name: &str) -> Vec<u8> {
+    // id, flags (rd), qdcount, ancount, nscount, arcount
+    let mut req = Vec::with_capacity(32);
+    req.extend_from_slice(&id.to_be_bytes());
+    req.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+
+    for label in name.split('.') {
+        req.push(label.len() as u8);
+        req.extend_from_slice(label.as_bytes());
+    }
+    req.push(0);
+
+    req.extend_from_slice(&DNS_TYPE_TXT.to_be_bytes());
+    req.extend_from_slice(&DNS_CLASS_CH.to_be_bytes());
+
+    req
+}
+
+fn skip_name(resp: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *resp.get(offset)? as usize;
+        match len {
+            0 => return Some(offset + 1),
+            // compression pointer
+            0xc0.. => return Some(offset + 2),
+            _ => offset += 1 + len,
+        }
+    }
+}
+
+fn parse_chaos_response(id: u16, resp: &[u8]) -> Option<u64> {
+    if resp.len() < 12 || resp[0..2] != id.to_be_bytes() {
+        return None;
+    }
+    let ancount = u16::from_be_bytes([resp[6], resp[7]]);
+    if ancount == 0 {
+        return None;
+    }
+
+    // skip the question, then the answer name, type, class, and ttl
+    let offset = skip_name(resp, 12)? + 4;
+    let offset = skip_name(resp, offset)? + 8;
+    let rdlen = u16::from_be_bytes([*resp.get(offset)?, *resp.get(offset + 1)?]) as usize;
+    let rdata = resp.get(offset + 2..offset + 2 + rdlen)?;
+
+    // a single character-string
+    let len = *rdata.first()? as usize;
+    let txt = std::str::from_utf8(rdata.get(1..1 + len)?).ok()?;
+    txt.parse().ok()
+}
+
+pub(super) struct Dnsmasq {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    lease_path: &'static path::Path,
+    dns_addr: Option<net::SocketAddr>,
+    refresh_interval: Option<time::Duration>,
+    max_staleness: time::Duration,
+    stats: sync::Mutex<Option<Stats>>,
+    cache: sync::Mutex<Option<CacheStats>>,
+    up: sync::atomic::AtomicBool,
+    // bumped on each scrape; the task coalesces pending requests
+    refresh: tokio::sync::watch::Sender<u64>,
+    // the last refresh seq the task has finished
+    refreshed: tokio::sync::watch::Sender<u64>,
+    synchronous: bool,
+}
+
+impl Dnsmasq {
+    pub fn new(errors: sync::Arc<collector::ErrorCounter>) -> sync::Arc<Self> {
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        let dnsmasq = Dnsmasq {
+            errors,
+            lease_path: &config::get().dnsmasq_lease_file,
+            dns_addr: config::get().dnsmasq_dns_addr,
+            refresh_interval: config::get().refresh_interval,
+            max_staleness: config::get().max_staleness,
+            stats: sync::Mutex::new(None),
+            cache: sync::Mutex::new(None),
+            up: sync::atomic::AtomicBool::new(false),
+            refresh,
+            refreshed,
+            synchronous: config::get().synchronous,
+        };
+        let dnsmasq = sync::Arc::new(dnsmasq);
+
+        let clone = dnsmasq.clone();
+        tokio::task::spawn(async move {
+            clone.task(refresh_rx).await;
+        });
+
+        dnsmasq
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.stats.lock().unwrap().is_some()
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("dnsmasq") {
+            return;
+        }
+
+        let up = self.up.load(sync::atomic::Ordering::Relaxed);
+        enc.write(&metrics.net.dhcp_up, up as u64, None);
+
+        // stop re-emitting old stats such that the series go stale
+        let is_fresh = |timestamp: time::SystemTime| {
+            timestamp
+                .elapsed()
+                .is_ok_and(|age| age <= self.max_staleness)
+        };
+        let stats = self.stats.lock().unwrap();
+        let stats = stats.as_ref().filter(|stats| is_fresh(stats.timestamp));
+        let cache = self.cache.lock().unwrap();
+        let cache = cache.as_ref().filter(|cache| is_fresh(cache.timestamp));
+
+        if let Some(stats) = stats {
+            enc.write(
+                &metrics.net.dhcp_active_leases,
+                stats.active_leases,
+                Some(stats.timestamp),
+            );
+        }
+
+        if let Some(cache) = cache {
+            enc.write(
+                &metrics.net.dns_cache_hits,
+                cache.hits,
+                Some(cache.timestamp),
+            );
+            enc.write(
+                &metrics.net.dns_cache_misses,
+                cache.misses,
+                Some(cache.timestamp),
+            );
+        }
+
+        // in synchronous mode, refreshes are requested before collecting
+        if !self.synchronous {
+            self.request_refresh();
+        }
+    }
+
+    pub fn request_refresh(&self) -> u64 {
+        self.refresh.send_modify(|seq| *seq += 1);
+        *self.refresh.borrow()
+    }
+
+    pub async fn wait_refreshed(&self, seq: u64) {
+        let mut refreshed = self.refreshed.subscribe();
+        let _ = refreshed.wait_for(|refreshed| *refreshed >= seq).await;
+    }
+
+    async fn task(&self, mut refresh: tokio::sync::watch::Receiver<u64>) {
+        loop {
+            let seq = *refresh.borrow_and_update();
+            let res = self.parse_stats().await;
+            self.errors.update("dnsmasq", &res);
+            self.up.store(res.is_ok(), sync::atomic::Ordering::Relaxed);
+            match res {
+                Ok(stats) => *self.stats.lock().unwrap() = Some(stats),
+                Err(err) => {
+                    let level = match err {
+                        collector::CollectorError::NotFound(_) => log::Level::Debug,
+                        _ => log::Level::Error,
+                    };
+
+                    self.errors.log(
+                        "dnsmasq",
+                        level,
+                        format!("failed to collect dnsmasq stats: {err:?}"),
+                    );
+                }
+            }
+
+            // the cache stats are best-effort and do not affect dhcp_up
+            if let Some(addr) = self.dns_addr {
+                let res = self.parse_cache_stats(addr).await;
+                self.errors.update("dnsmasq_cache", &res);
+                match res {
+                    Ok(cache) => *self.cache.lock().unwrap() = Some(cache),
+                    Err(err) => self.errors.log(
+                        "dnsmasq_cache",
+                        log::Level::Error,
+                        format!("failed to collect dnsmasq cache stats: {err:?}"),
+                    ),
+                }
+            }
+
+            self.refreshed.send_replace(seq);
+
+            // refresh on scrape or, if configured, periodically
+            let res = match self.refresh_interval {
+                Some(interval) => tokio::time::timeout(interval, refresh.changed())
+                    .await
+                    .unwrap_or(Ok(())),
+                None => refresh.changed().await,
+            };
+            if res.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn query_chaos(&self, addr: net::SocketAddr, name: &str) -> Result<u64> {
+        let bind_addr: net::SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let sock = tokio::net::UdpSocket::bind(bind_addr)
+            .await
+            .context("failed to bind udp socket")?;
+
+        let id = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |dur| dur.subsec_nanos() as u16);
+        sock.send_to(&build_chaos_query(id, name), addr)
+            .await
+            .with_context(|| format!("failed to query {name}"))?;
+
+        let mut buf = [0; 512];
+        let len = tokio::time::timeout(DNS_TIMEOUT, sock.recv(&mut buf))
+            .await
+            .with_context(|| format!("timed out querying {name}"))?
+            .with_context(|| format!("failed to query {name}"))?;
+
+        parse_chaos_response(id, &buf[..len]).ok_or_else(|| anyhow!("bad response for {name}"))
+    }
+
+    async fn parse_stats(&self) -> Result<Stats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let data = tokio::fs::read_to_string(self.lease_path)
+            .await
+            .with_context(|| format!("failed to read {:?}", self.lease_path))?;
+
+        // expiry mac ip hostname client-id; expiry 0 means infinite
+        let now = timestamp
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |dur| dur.as_secs());
+        let active_leases = data
+            .lines()
+            .filter_map(|line| line.split_ascii_whitespace().next()?.parse::<u64>().ok())
+            .filter(|&expiry| expiry == 0 || expiry > now)
+            .count() as u64;
+
+        Ok(Stats {
+            timestamp,
+            active_leases,
+        })
+    }
+
+    // dnsmasq answers cache stats to CHAOS TXT queries
+    async fn parse_cache_stats(
+        &self,
+        addr: net::SocketAddr,
+    ) -> Result<CacheStats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let hits = self.query_chaos(addr, "hits.bind").await?;
+        let misses = self.query_chaos(addr, "misses.bind").await?;
+
+        Ok(CacheStats {
+            timestamp,
+            hits,
+            misses,
+        })
+    }
+}
@@ -4,48 +4,164 @@
 use crate::{collector, config, metric};
 use anyhow::{Context, Result, anyhow};
 use serde_json::{self, Value, json};
-use std::{io, path, sync, time};
+use std::{cmp, collections, io, path, sync, time};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+const MIN_RETRY_BACKOFF: time::Duration = time::Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: time::Duration = time::Duration::from_secs(60);
+
 struct Stats {
     timestamp: time::SystemTime,
     pkt4_received: u64,
     pkt4_sent: u64,
     v4_allocation_fail: u64,
+    assigned_addresses: u64,
+}
+
+struct Stats6 {
+    timestamp: time::SystemTime,
+    pkt6_release_received: u64,
+    // v6-ia-pd-<event> counters
+    pd_events: Vec<(String, u64)>,
+    // subnet id and subnet[id].assigned-pds
+    assigned_pds: Vec<(String, u64)>,
+}
+
+// indexed by the state column of the memfile lease file
+const LEASE_STATES: [&str; 4] = ["default", "declined", "expired-reclaimed", "released"];
+
+// adds address to expire and state of the records of a lease file
+fn parse_lease_records(
+    data: &str,
+    records: &mut collections::HashMap<String, (u64, usize)>,
+) -> Result<()> {
+    // the columns differ between lease4 and lease6 files
+    let mut lines = data.lines();
+    let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+    let col = |name| {
+        header
+            .iter()
+            .position(|&col| col == name)
+            .ok_or_else(|| anyhow!("lease file has no {name} column"))
+    };
+    let [address_col, expire_col, state_col] = [col("address")?, col("expire")?, col("state")?];
+
+    // the file is append-only and the last record of an address wins
+    for line in lines {
+        let cols: Vec<&str> = line.split(',').collect();
+        let (Some(address), Some(expire), Some(state)) = (
+            cols.get(address_col),
+            cols.get(expire_col).and_then(|col| col.parse::<u64>().ok()),
+            cols.get(state_col)
+                .and_then(|col| col.parse::<usize>().ok()),
+        ) else {
+            continue;
+        };
+        records.insert(address.to_string(), (expire, state));
+    }
+
+    Ok(())
+}
+
+struct LeaseStats {
+    timestamp: time::SystemTime,
+    active: u64,
+    states: [u64; LEASE_STATES.len()],
 }
 
 pub(super) struct Kea {
+    errors: sync::Arc<collector::ErrorCounter>,
+
     path: &'static path::Path,
+    path6: Option<&'static path::Path>,
+    lease_path: Option<&'static path::Path>,
+    max_response_size: u64,
+    refresh_interval: Option<time::Duration>,
+    max_staleness: time::Duration,
     req: Vec<u8>,
     stats: sync::Mutex<Option<Stats>>,
-    notify: tokio::sync::Notify,
+    stats6: sync::Mutex<Option<Stats6>>,
+    leases: sync::Mutex<Option<LeaseStats>>,
+    // active lease count changes between refreshes
+    lease_changes: sync::atomic::AtomicU64,
+    up: sync::atomic::AtomicBool,
+    // bumped on each scrape; the task coalesces pending requests
+    refresh: tokio::sync::watch::Sender<u64>,
+    // the last refresh seq the task has finished
+    refreshed: tokio::sync::watch::Sender<u64>,
+    synchronous: bool,
 }
 
 impl Kea {
-    pub fn new() -> Result<sync::Arc<Self>> {
+    pub fn new(errors: sync::Arc<collector::ErrorCounter>) -> Result<sync::Arc<Self>> {
         let req = json!({
             "command": "statistic-get-all"
         });
         let req = serde_json::to_vec(&req)?;
 
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
         let kea = Kea {
+            errors,
             path: &config::get().kea_socket,
+            path6: config::get().kea6_socket.as_deref(),
+            lease_path: config::get().kea_lease_file.as_deref(),
+            max_response_size: config::get().max_response_size,
+            refresh_interval: config::get().refresh_interval,
+            max_staleness: config::get().max_staleness,
             req,
             stats: sync::Mutex::new(None),
-            notify: tokio::sync::Notify::new(),
+            stats6: sync::Mutex::new(None),
+            leases: sync::Mutex::new(None),
+            lease_changes: sync::atomic::AtomicU64::new(0),
+            up: sync::atomic::AtomicBool::new(false),
+            refresh,
+            refreshed,
+            synchronous: config::get().synchronous,
         };
         let kea = sync::Arc::new(kea);
 
         let clone = kea.clone();
         tokio::task::spawn(async move {
-            clone.task().await;
+            clone.task(refresh_rx).await;
         });
 
         Ok(kea)
     }
 
-    pub fn collect(&self, metrics: &collector::Metrics, enc: &mut metric::Encoder) {
-        if let Some(stats) = &*self.stats.lock().unwrap() {
+    pub fn is_ready(&self) -> bool {
+        self.stats.lock().unwrap().is_some()
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("kea") {
+            return;
+        }
+
+        let up = self.up.load(sync::atomic::Ordering::Relaxed);
+        enc.write(&metrics.net.dhcp_up, up as u64, None);
+
+        // stop re-emitting old stats such that the series go stale
+        let is_fresh = |timestamp: time::SystemTime| {
+            timestamp
+                .elapsed()
+                .is_ok_and(|age| age <= self.max_staleness)
+        };
+
+        let stats = self.stats.lock().unwrap();
+        let stats = stats.as_ref().filter(|stats| is_fresh(stats.timestamp));
+        let stats6 = self.stats6.lock().unwrap();
+        let stats6 = stats6.as_ref().filter(|stats6| is_fresh(stats6.timestamp));
+        let leases = self.leases.lock().unwrap();
+        let leases = leases.as_ref().filter(|leases| is_fresh(leases.timestamp));
+
+        if let Some(stats) = stats {
             enc.write(
                 &metrics.net.dhcp_received,
                 stats.pkt4_received,
@@ -61,46 +177,185 @@ impl Kea {
                 stats.v4_allocation_fail,
                 Some(stats.timestamp),
             );
+            enc.write(
+                &metrics.net.dhcp_active_leases,
+                stats.assigned_addresses,
+                Some(stats.timestamp),
+            );
+            enc.write(
+                &metrics.net.dhcp_lease_change,
+                self.lease_changes.load(sync::atomic::Ordering::Relaxed),
+                Some(stats.timestamp),
+            );
         }
 
-        self.notify.notify_one();
+        if let Some(stats6) = stats6 {
+            enc.write(
+                &metrics.net.dhcp6_release_received,
+                stats6.pkt6_release_received,
+                Some(stats6.timestamp),
+            );
+
+            let mut menc = enc.with_info(&metrics.net.dhcp6_pd_events, Some(stats6.timestamp));
+            for (event, count) in &stats6.pd_events {
+                menc.write(&[event], count);
+            }
+
+            let mut menc =
+                enc.with_info(&metrics.net.dhcp6_assigned_prefixes, Some(stats6.timestamp));
+            for (subnet, count) in &stats6.assigned_pds {
+                menc.write(&[subnet], count);
+            }
+        }
+
+        if let Some(leases) = leases {
+            // the lease file is a fallback when the control socket is down
+            if stats.is_none() {
+                enc.write(
+                    &metrics.net.dhcp_active_leases,
+                    leases.active,
+                    Some(leases.timestamp),
+                );
+            }
+
+            let mut menc = enc.with_info(&metrics.net.dhcp_leases, Some(leases.timestamp));
+            for (state, count) in LEASE_STATES.iter().zip(leases.states) {
+                menc.write(&[state], count);
+            }
+        }
+
+        // in synchronous mode, refreshes are requested before collecting
+        if !self.synchronous {
+            self.request_refresh();
+        }
+    }
+
+    pub fn request_refresh(&self) -> u64 {
+        self.refresh.send_modify(|seq| *seq += 1);
+        *self.refresh.borrow()
+    }
+
+    pub async fn wait_refreshed(&self, seq: u64) {
+        let mut refreshed = self.refreshed.subscribe();
+        let _ = refreshed.wait_for(|refreshed| *refreshed >= seq).await;
     }
 
-    async fn task(&self) {
+    async fn task(&self, mut refresh: tokio::sync::watch::Receiver<u64>) {
+        let mut backoff = None;
         loop {
-            match self.parse_stats().await {
-                Ok(stats) => *self.stats.lock().unwrap() = Some(stats),
+            let seq = *refresh.borrow_and_update();
+            let mut failed = false;
+
+            let res = self.parse_stats().await;
+            self.errors.update("kea", &res);
+            self.up.store(res.is_ok(), sync::atomic::Ordering::Relaxed);
+            match res {
+                Ok(stats) => {
+                    let mut old = self.stats.lock().unwrap();
+                    if old
+                        .as_ref()
+                        .is_some_and(|old| old.assigned_addresses != stats.assigned_addresses)
+                    {
+                        self.lease_changes
+                            .fetch_add(1, sync::atomic::Ordering::Relaxed);
+                    }
+                    *old = Some(stats);
+                }
                 Err(err) => {
-                    let mut level = log::Level::Error;
-                    if let Some(err) = err.downcast_ref::<io::Error>() {
-                        if err.kind() == io::ErrorKind::NotFound {
-                            level = log::Level::Debug;
-                        }
+                    let level = match err {
+                        collector::CollectorError::NotFound(_) => log::Level::Debug,
+                        _ => log::Level::Error,
+                    };
+                    failed = true;
+
+                    self.errors.log(
+                        "kea",
+                        level,
+                        format!("failed to collect kea stats: {err:?}"),
+                    );
+                }
+            }
+
+            // dhcp4 and dhcp6 are separate daemons with their own sockets,
+            // and one being down does not affect the stats of the other
+            if let Some(path6) = self.path6 {
+                let res = self.parse_stats6(path6).await;
+                self.errors.update("kea6", &res);
+                match res {
+                    Ok(stats6) => *self.stats6.lock().unwrap() = Some(stats6),
+                    Err(err) => {
+                        let level = match err {
+                            collector::CollectorError::NotFound(_) => log::Level::Debug,
+                            _ => log::Level::Error,
+                        };
+                        failed = true;
+
+                        self.errors.log(
+                            "kea6",
+                            level,
+                            format!("failed to collect kea6 stats: {err:?}"),
+                        );
                     }
+                }
+            }
 
-                    log::log!(level, "failed to collect kea stats: {err:?}");
+            // retry without waiting for a scrape, such that a daemon starting
+            // after us is picked up promptly
+            backoff = failed.then(|| {
+                backoff.map_or(MIN_RETRY_BACKOFF, |backoff| {
+                    cmp::min(backoff * 2, MAX_RETRY_BACKOFF)
+                })
+            });
+
+            if let Some(lease_path) = self.lease_path {
+                let res = self.parse_leases(lease_path).await;
+                self.errors.update("kea_leases", &res);
+                match res {
+                    Ok(leases) => *self.leases.lock().unwrap() = Some(leases),
+                    Err(err) => self.errors.log(
+                        "kea_leases",
+                        log::Level::Error,
+                        format!("failed to collect kea leases: {err:?}"),
+                    ),
                 }
             }
 
-            self.notify.notified().await;
+            self.refreshed.send_replace(seq);
+
+            // refresh on scrape, on retry, or, if configured, periodically
+            let timeout = [self.refresh_interval, backoff].into_iter().flatten().min();
+            let res = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, refresh.changed())
+                    .await
+                    .unwrap_or(Ok(())),
+                None => refresh.changed().await,
+            };
+            if res.is_err() {
+                break;
+            }
         }
     }
 
-    async fn parse_stats(&self) -> Result<Stats> {
-        let mut sock = tokio::net::UnixStream::connect(&self.path)
+    async fn query(&self, path: &path::Path) -> Result<Value> {
+        let mut sock = tokio::net::UnixStream::connect(path)
             .await
-            .with_context(|| format!("failed to connect to {:?}", self.path))?;
-
-        let timestamp = time::SystemTime::now();
+            .with_context(|| format!("failed to connect to {path:?}"))?;
 
         sock.write_all(&self.req)
             .await
             .context("failed to write to kea")?;
 
         let mut buf = Vec::new();
-        sock.read_to_end(&mut buf)
+        sock.take(self.max_response_size + 1)
+            .read_to_end(&mut buf)
             .await
             .context("failed to read from kea")?;
+        if buf.len() as u64 > self.max_response_size {
+            return Err(anyhow!(
+                "kea response exceeds {} bytes",
+                self.max_response_size
+            ));
+        }
         let resp: Value = serde_json::from_slice(&buf).context("failed to parse kea response")?;
 
         let result = resp
@@ -111,6 +366,129 @@ impl Kea {
             return Err(anyhow!("kea responded result {result}"));
         }
 
+        Ok(resp)
+    }
+
+    async fn parse_leases(
+        &self,
+        path: &path::Path,
+    ) -> Result<LeaseStats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        // lfc leaves older records in <file>.2 and <file>.1 while it runs;
+        // read from the oldest such that the last record of an address wins
+        let mut records = collections::HashMap::new();
+        let mut remaining = self.max_response_size;
+        for suffix in [".2", ".1", ""] {
+            let mut file_path = path.as_os_str().to_owned();
+            file_path.push(suffix);
+            let file_path = path::PathBuf::from(file_path);
+
+            let file = match tokio::fs::File::open(&file_path).await {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound && !suffix.is_empty() => {
+                    continue;
+                }
+                Err(err) => {
+                    return Err(anyhow::Error::from(err)
+                        .context(format!("failed to open {file_path:?}"))
+                        .into());
+                }
+            };
+
+            let mut data = String::new();
+            file.take(remaining + 1)
+                .read_to_string(&mut data)
+                .await
+                .with_context(|| format!("failed to read {file_path:?}"))?;
+            if data.len() as u64 > remaining {
+                return Err(
+                    anyhow!("kea lease files exceed {} bytes", self.max_response_size).into(),
+                );
+            }
+            remaining -= data.len() as u64;
+
+            parse_lease_records(&data, &mut records)
+                .with_context(|| format!("failed to parse {file_path:?}"))?;
+        }
+
+        let now = timestamp
+            .duration_since(time::UNIX_EPOCH)
+            .map_or(0, |dur| dur.as_secs());
+        let mut active = 0;
+        let mut states = [0; LEASE_STATES.len()];
+        for (expire, state) in records.into_values() {
+            if let Some(count) = states.get_mut(state) {
+                *count += 1;
+            }
+            if state == 0 && expire > now {
+                active += 1;
+            }
+        }
+
+        Ok(LeaseStats {
+            timestamp,
+            active,
+            states,
+        })
+    }
+
+    async fn parse_stats6(&self, path6: &path::Path) -> Result<Stats6, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let resp = self.query(path6).await?;
+
+        let pkt6_release_received = resp
+            .pointer("/arguments/pkt6-release-received/0/0")
+            .and_then(Value::as_u64)
+            .unwrap_or_default();
+        // v6-ia-pd-lease-reuses and friends, which vary between kea versions
+        let mut pd_events: Vec<(String, u64)> = resp
+            .pointer("/arguments")
+            .and_then(Value::as_object)
+            .map(|args| {
+                args.iter()
+                    .filter_map(|(key, val)| {
+                        let event = key.strip_prefix("v6-ia-pd-")?.replace('-', "_");
+                        let count = val.pointer("/0/0").and_then(Value::as_u64)?;
+                        Some((event, count))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        pd_events.sort();
+        // subnet[id].assigned-pds, excluding the per-pool stats
+        let mut assigned_pds: Vec<(String, u64)> = resp
+            .pointer("/arguments")
+            .and_then(Value::as_object)
+            .map(|args| {
+                args.iter()
+                    .filter_map(|(key, val)| {
+                        let id = key
+                            .strip_prefix("subnet[")
+                            .and_then(|key| key.strip_suffix("].assigned-pds"))
+                            .filter(|id| id.parse::<u64>().is_ok())?;
+                        let count = val.pointer("/0/0").and_then(Value::as_u64)?;
+                        Some((id.to_string(), count))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        assigned_pds.sort();
+
+        Ok(Stats6 {
+            timestamp,
+            pkt6_release_received,
+            pd_events,
+            assigned_pds,
+        })
+    }
+
+    async fn parse_stats(&self) -> Result<Stats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let resp = self.query(self.path).await?;
+
         let pkt4_received = resp
             .pointer("/arguments/pkt4-received/0/0")
             .and_then(Value::as_u64)
@@ -123,12 +501,123 @@ impl Kea {
             .pointer("/arguments/v4-allocation-fail/0/0")
             .and_then(Value::as_u64)
             .unwrap_or_default();
+        // sum of subnet[id].assigned-addresses, excluding the per-pool stats
+        let assigned_addresses = resp
+            .pointer("/arguments")
+            .and_then(Value::as_object)
+            .map(|args| {
+                args.iter()
+                    .filter(|(key, _)| {
+                        key.strip_prefix("subnet[")
+                            .and_then(|key| key.strip_suffix("].assigned-addresses"))
+                            .is_some_and(|id| id.parse::<u64>().is_ok())
+                    })
+                    .filter_map(|(_, val)| val.pointer("/0/0").and_then(Value::as_u64))
+                    .sum()
+            })
+            .unwrap_or_default();
 
         Ok(Stats {
             timestamp,
             pkt4_received,
             pkt4_sent,
             v4_allocation_fail,
+            assigned_addresses,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(path: &'static path::Path) -> sync::Arc<Kea> {
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        let kea = sync::Arc::new(Kea {
+            errors: Default::default(),
+            path,
+            path6: None,
+            lease_path: None,
+            max_response_size: 4096,
+            refresh_interval: None,
+            max_staleness: time::Duration::from_secs(300),
+            req: b"{}".to_vec(),
+            stats: sync::Mutex::new(None),
+            stats6: sync::Mutex::new(None),
+            leases: sync::Mutex::new(None),
+            lease_changes: sync::atomic::AtomicU64::new(0),
+            up: sync::atomic::AtomicBool::new(false),
+            refresh,
+            refreshed,
+            synchronous: true,
+        });
+
+        let clone = kea.clone();
+        tokio::task::spawn(async move {
+            clone.task(refresh_rx).await;
+        });
+
+        kea
+    }
+
+    // a kea whose pkt4-received is the number of queries so far
+    async fn serve(
+        listener: tokio::net::UnixListener,
+        queries: sync::Arc<sync::atomic::AtomicU64>,
+    ) {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            let mut buf = [0; 256];
+            let _ = sock.read(&mut buf).await;
+
+            let count = queries.fetch_add(1, sync::atomic::Ordering::Relaxed) + 1;
+            let resp = json!({
+                "result": 0,
+                "arguments": {
+                    "pkt4-received": [[count, "2025-01-01 00:00:00.000000"]],
+                },
+            });
+            let _ = sock.write_all(&serde_json::to_vec(&resp).unwrap()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_on_scrape() {
+        let path = std::env::temp_dir().join(format!("kea-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let queries = sync::Arc::new(sync::atomic::AtomicU64::new(0));
+        tokio::task::spawn(serve(listener, queries.clone()));
+
+        let kea = start(Box::leak(path.clone().into_boxed_path()));
+        let scrape =
+            || collector::render(|metrics, enc| kea.collect(metrics, &Default::default(), enc));
+
+        // each scrape sees the stats queried after it started
+        for _ in 0..3 {
+            let seq = kea.request_refresh();
+            kea.wait_refreshed(seq).await;
+
+            let count = queries.load(sync::atomic::Ordering::Relaxed);
+            let text = scrape();
+            assert!(text.contains(&format!(
+                "test_network_dhcp_received_packets_total {count}\n"
+            )));
+        }
+
+        // pending requests are coalesced
+        let last = queries.load(sync::atomic::Ordering::Relaxed);
+        kea.request_refresh();
+        let seq = kea.request_refresh();
+        kea.wait_refreshed(seq).await;
+
+        let count = queries.load(sync::atomic::Ordering::Relaxed);
+        assert!(count > last && count <= last + 2);
+        assert!(scrape().contains(&format!(
+            "test_network_dhcp_received_packets_total {count}\n"
+        )));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -5,26 +5,53 @@ mod ethtool;
 mod nfnetlink;
 mod procfs;
 mod rtnetlink;
+mod sysctl;
 mod sysfs;
 
 use crate::{collector, config, metric};
 use anyhow::{Context, Result};
-use log::error;
 use neli::{consts::socket::NlFamily, router::synchronous::NlRouter};
-use std::{fs, io, path};
+use std::{collections, fs, io, iter, mem, path, sync, time};
 
 pub(super) struct Linux {
+    errors: sync::Arc<collector::ErrorCounter>,
+
     procfs_path: &'static path::Path,
     sysfs_path: &'static path::Path,
+    devfs_path: &'static path::Path,
 
-    rt_sock: NlRouter,
-    nf_sock: NlRouter,
-    genl_sock: NlRouter,
+    // None when the kernel lacks the netlink family
+    rt_sock: Option<NlRouter>,
+    nf_sock: Option<NlRouter>,
+    genl_sock: Option<NlRouter>,
 
-    ethtool_id: u16,
+    // None when the kernel lacks the ethtool genl family
+    ethtool_id: Option<u16>,
 
     sysconf_page_size: u64,
     sysconf_user_hz: u64,
+
+    cpu_usage: bool,
+    fs_include_virtual: bool,
+    link_rate: bool,
+    device_include: Option<&'static regex::Regex>,
+    device_exclude: Option<&'static regex::Regex>,
+    sysctl_keys: &'static [String],
+    // cpu name to busy and total ticks of the last scrape
+    cpu_ticks: sync::Mutex<collections::HashMap<String, (u64, u64)>>,
+
+    // swapped in and out pages of the last scrape
+    swap_pages: sync::Mutex<Option<(time::Instant, u64, u64)>>,
+    // tcp out and retransmitted segments of the last scrape
+    tcp_segs: sync::Mutex<Option<(u64, u64)>>,
+    // link name to ifindex, rx bytes, and tx bytes of the last scrape
+    link_bytes: sync::Mutex<collections::HashMap<String, (time::Instant, i32, u64, u64)>>,
+    // ppp device name to ifindex and session start time
+    ppp_sessions: sync::Mutex<collections::HashMap<String, (i32, time::SystemTime)>>,
+    // up link name to ifindex, carrier up count, and when it was first seen up
+    link_up_since: sync::Mutex<collections::HashMap<String, (i32, u32, time::SystemTime)>>,
+    // thermal zone name to last temperature and throttle event count
+    thermal_throttles: sync::Mutex<collections::HashMap<String, (u64, u64)>>,
 }
 
 fn read_string(path: impl AsRef<path::Path>) -> Result<String> {
@@ -47,103 +74,402 @@ fn nl_socket(family: NlFamily) -> Result<NlRouter> {
     Ok(sock)
 }
 
-impl Linux {
-    pub fn new() -> Result<Self> {
-        let config = config::get();
+// router kernels vary in which netlink families are built in; the collectors
+// using a missing family are skipped
+fn nl_sockets() -> (Option<NlRouter>, Option<NlRouter>, Option<NlRouter>) {
+    let open = |family, name| {
+        nl_socket(family)
+            .inspect_err(|err| log::warn!("failed to open {name} socket: {err:?}"))
+            .ok()
+    };
+
+    (
+        open(NlFamily::Route, "rtnetlink"),
+        open(NlFamily::Netfilter, "nfnetlink"),
+        open(NlFamily::Generic, "generic netlink"),
+    )
+}
 
-        let rt_sock = nl_socket(NlFamily::Route)?;
-        let nf_sock = nl_socket(NlFamily::Netfilter)?;
-        let genl_sock = nl_socket(NlFamily::Generic)?;
+// returns the rtnetlink, nfnetlink, and generic netlink sockets, and the
+// ethtool family id
+#[allow(clippy::type_complexity)]
+fn open_sockets() -> Result<(
+    Option<NlRouter>,
+    Option<NlRouter>,
+    Option<NlRouter>,
+    Option<u16>,
+)> {
+    // sockets stay in the namespace they are created in
+    let orig_netns = config::get()
+        .netns
+        .as_deref()
+        .map(enter_netns)
+        .transpose()?;
+    let socks = nl_sockets();
+    if let Some(orig_netns) = orig_netns {
+        crate::libc::setns_net(&orig_netns).context("failed to restore netns")?;
+    }
+    let (rt_sock, nf_sock, genl_sock) = socks;
+
+    // ethtool netlink requires CONFIG_ETHTOOL_NETLINK; the other collectors
+    // work without it
+    let ethtool_id = genl_sock.as_ref().and_then(|genl_sock| {
+        genl_sock
+            .resolve_genl_family(ethtool::ETHTOOL_GENL_NAME)
+            .inspect_err(|err| {
+                log::warn!(
+                    "failed to resolve ethtool genl family; disabling ethtool collectors: {err}"
+                )
+            })
+            .ok()
+    });
+
+    Ok((rt_sock, nf_sock, genl_sock, ethtool_id))
+}
 
-        let ethtool_id = genl_sock.resolve_genl_family(ethtool::ETHTOOL_GENL_NAME)?;
+// returns the original namespace of the thread
+fn enter_netns(name: &str) -> Result<fs::File> {
+    let orig_path = "/proc/thread-self/ns/net";
+    let orig = fs::File::open(orig_path).with_context(|| format!("failed to open {orig_path}"))?;
+
+    let path = path::Path::new("/var/run/netns").join(name);
+    let ns = fs::File::open(&path).with_context(|| format!("failed to open {path:?}"))?;
+    crate::libc::setns_net(&ns).with_context(|| format!("failed to enter netns {name}"))?;
+
+    Ok(orig)
+}
+
+// the key of an element can be an address, a range, or a concatenation
+fn write_nft_set_counters(
+    menc: &mut metric::MetricEncoder<'_, 4>,
+    set: &nfnetlink::NftSet,
+    counters: nfnetlink::NftSetCounterIter,
+) -> Result<(), collector::CollectorError> {
+    for counter in counters {
+        let counter = counter.map_err(collector::CollectorError::netlink)?;
+
+        menc.write(
+            &[
+                nfnetlink::nft_family_name(set.family),
+                &set.table,
+                &set.name,
+                &counter.addr,
+            ],
+            counter.bytes,
+        );
+    }
+
+    Ok(())
+}
+
+impl Linux {
+    pub fn new(errors: sync::Arc<collector::ErrorCounter>) -> Result<Self> {
+        let config = config::get();
+        let (rt_sock, nf_sock, genl_sock, ethtool_id) = open_sockets()?;
 
         let lin = Linux {
-            procfs_path: config.procfs_path,
-            sysfs_path: config.sysfs_path,
+            errors,
+            procfs_path: &config.procfs_path,
+            sysfs_path: &config.sysfs_path,
+            devfs_path: &config.devfs_path,
             rt_sock,
             nf_sock,
             genl_sock,
             ethtool_id,
             sysconf_page_size: crate::libc::sysconf_page_size(),
             sysconf_user_hz: crate::libc::sysconf_user_hz(),
+            cpu_usage: config.cpu_usage,
+            fs_include_virtual: config.fs_include_virtual,
+            link_rate: config.link_rate,
+            device_include: config.device_include.as_ref(),
+            device_exclude: config.device_exclude.as_ref(),
+            sysctl_keys: &config.sysctl_keys,
+            cpu_ticks: Default::default(),
+            swap_pages: Default::default(),
+            tcp_segs: Default::default(),
+            link_bytes: Default::default(),
+            ppp_sessions: Default::default(),
+            link_up_since: Default::default(),
+            thermal_throttles: Default::default(),
         };
 
         Ok(lin)
     }
 
-    pub fn collect(&self, metrics: &collector::Metrics, enc: &mut metric::Encoder) {
-        if let Err(err) = self.collect_cpu(metrics, enc) {
-            error!("failed to collect cpu metrics: {err:?}");
+    // reads the fixture trees under tests/fixtures and opens no socket
+    #[cfg(test)]
+    pub fn with_fixtures(procfs: &str, sysfs: &str) -> Self {
+        Linux {
+            errors: Default::default(),
+            procfs_path: collector::fixture_path(procfs),
+            sysfs_path: collector::fixture_path(sysfs),
+            devfs_path: collector::fixture_path("devfs"),
+            rt_sock: None,
+            nf_sock: None,
+            genl_sock: None,
+            ethtool_id: None,
+            sysconf_page_size: 4096,
+            sysconf_user_hz: 100,
+            cpu_usage: true,
+            fs_include_virtual: false,
+            link_rate: true,
+            device_include: None,
+            device_exclude: None,
+            sysctl_keys: &[],
+            cpu_ticks: Default::default(),
+            swap_pages: Default::default(),
+            tcp_segs: Default::default(),
+            link_bytes: Default::default(),
+            ppp_sessions: Default::default(),
+            link_up_since: Default::default(),
+            thermal_throttles: Default::default(),
         }
+    }
 
-        if let Err(err) = self.collect_mem_info(metrics, enc) {
-            error!("failed to collect mem info metrics: {err:?}");
-        }
+    // per-scrape state, such as ppp session start times, is kept
+    pub fn reopen(&mut self) -> Result<()> {
+        (self.rt_sock, self.nf_sock, self.genl_sock, self.ethtool_id) = open_sockets()?;
 
-        if let Err(err) = self.collect_mem_vm(metrics, enc) {
-            error!("failed to collect mem vm metrics: {err:?}");
-        }
+        Ok(())
+    }
 
-        if let Err(err) = self.collect_fs(metrics, enc) {
-            error!("failed to collect fs metrics: {err:?}");
-        }
+    fn device_matches(&self, name: &str) -> bool {
+        self.device_include.is_none_or(|re| re.is_match(name))
+            && !self.device_exclude.is_some_and(|re| re.is_match(name))
+    }
 
-        if let Err(err) = self.collect_thermal(metrics, enc) {
-            error!("failed to collect thermal metrics: {err:?}");
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        self.errors.run(filter, "host", "host info", || {
+            self.collect_host(metrics, enc)
+        });
+
+        self.errors.run(filter, "hardware", "hardware info", || {
+            self.collect_hardware(metrics, enc)
+        });
+
+        self.errors.run(filter, "cpu", "cpu metrics", || {
+            self.collect_cpu(metrics, enc)
+        });
+
+        self.errors.run(filter, "clock", "clock metrics", || {
+            self.collect_clock(metrics, enc)
+        });
+
+        self.errors.run(filter, "mem_info", "mem info metrics", || {
+            self.collect_mem_info(metrics, enc)
+        });
+
+        self.errors.run(filter, "mem_vm", "mem vm metrics", || {
+            self.collect_mem_vm(metrics, enc)
+        });
+
+        self.errors.run(filter, "mem_zram", "mem zram metrics", || {
+            self.collect_mem_zram(metrics, enc)
+        });
+
+        self.errors
+            .run(filter, "fs", "fs metrics", || self.collect_fs(metrics, enc));
+
+        self.errors.run(filter, "thermal", "thermal metrics", || {
+            self.collect_thermal(metrics, enc)
+        });
+
+        self.errors.run(filter, "hwmon", "hwmon metrics", || {
+            self.collect_hwmon(metrics, enc)
+        });
+
+        self.errors.run(filter, "fs_flash", "flash wear", || {
+            self.collect_fs_flash(metrics, enc)
+        });
+
+        if !self.sysctl_keys.is_empty() {
+            self.errors.run(filter, "sysctl", "sysctl", || {
+                self.collect_sysctl(metrics, enc)
+            });
         }
 
-        if let Err(err) = self.collect_net_link_speed(metrics, enc) {
-            error!("failed to collect net link speed: {err:?}");
+        if self.ethtool_id.is_some() {
+            self.errors
+                .run(filter, "net_link_speed", "net link speed", || {
+                    self.collect_net_link_speed(metrics, enc)
+                });
+
+            self.errors
+                .run(filter, "net_link_ring", "net link ring", || {
+                    self.collect_net_link_ring(metrics, enc)
+                });
+
+            self.errors
+                .run(filter, "net_link_pause", "net link pause", || {
+                    self.collect_net_link_pause(metrics, enc)
+                });
+
+            self.errors.run(filter, "net_link_eee", "net link eee", || {
+                self.collect_net_link_eee(metrics, enc)
+            });
+
+            self.errors
+                .run(filter, "net_link_tsinfo", "net link tsinfo", || {
+                    self.collect_net_link_tsinfo(metrics, enc)
+                });
+
+            self.errors
+                .run(filter, "net_link_stat", "net link stat", || {
+                    self.collect_net_link_stat(metrics, enc)
+                });
         }
 
-        if let Err(err) = self.collect_net_link_state(metrics, enc) {
-            error!("failed to collect net link state: {err:?}");
+        if self.rt_sock.is_some() {
+            self.errors
+                .run(filter, "net_link_state", "net link state", || {
+                    self.collect_net_link_state(metrics, enc)
+                });
+
+            self.errors.run(filter, "net_route", "net route", || {
+                self.collect_net_route(metrics, enc)
+            });
         }
 
-        if let Err(err) = self.collect_net_route(metrics, enc) {
-            error!("failed to collect net route: {err:?}");
+        self.errors.run(filter, "net_snmp", "net snmp", || {
+            self.collect_net_snmp(metrics, enc)
+        });
+
+        self.errors.run_with_level(
+            filter,
+            "net_snmp6",
+            "net snmp6",
+            |err| match err {
+                // ipv6 is disabled
+                collector::CollectorError::NotFound(_) => log::Level::Debug,
+                _ => log::Level::Error,
+            },
+            || self.collect_net_snmp6(metrics, enc),
+        );
+
+        self.errors.run(filter, "net_tcp", "net tcp", || {
+            self.collect_net_tcp(metrics, enc)
+        });
+
+        self.errors.run(filter, "net_listen", "net listen", || {
+            self.collect_net_listen(metrics, enc)
+        });
+
+        self.errors
+            .run(filter, "net_ephemeral_ports", "net ephemeral ports", || {
+                self.collect_net_ephemeral_ports(metrics, enc)
+            });
+
+        self.errors.run(filter, "net_softnet", "net softnet", || {
+            self.collect_net_softnet(metrics, enc)
+        });
+
+        if self.nf_sock.is_some() {
+            self.errors.run_with_level(
+                filter,
+                "net_nft",
+                "net nft",
+                |err| match err {
+                    collector::CollectorError::PermissionDenied(_) => log::Level::Debug,
+                    _ => log::Level::Error,
+                },
+                || self.collect_net_nft(metrics, enc),
+            );
         }
+    }
 
-        if let Err(err) = self.collect_net_nft(metrics, enc) {
-            let mut level = log::Level::Error;
-            if let Some(err) = err.downcast_ref::<io::Error>() {
-                if err.kind() == io::ErrorKind::PermissionDenied {
-                    level = log::Level::Debug;
-                }
-            }
+    fn collect_host(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let info = self.parse_sys_kernel()?;
+
+        enc.with_info(&metrics.system.host_info, None)
+            .write_info(&[&info.hostname, &info.osrelease]);
 
-            log::log!(level, "failed to collect net nft: {err:?}");
+        Ok(())
+    }
+
+    fn collect_hardware(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        if let Some(hw) = self.parse_hardware()? {
+            enc.with_info(&metrics.system.hardware_info, None)
+                .write_info(&[&hw.model, &hw.vendor]);
         }
+
+        Ok(())
     }
 
-    fn collect_cpu(&self, metrics: &collector::Metrics, enc: &mut metric::Encoder) -> Result<()> {
-        let stats = self.parse_stat()?;
+    fn collect_cpu(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let stats = self.parse_stat()?.collect::<Result<Vec<_>>>()?;
 
-        let mut cpus = Vec::new();
         let mut menc = enc.with_info(&metrics.cpu.idle, None);
-        for stat in stats {
-            let stat = stat?;
-
+        for stat in &stats {
             let idle_s = stat.idle_ticks as f64 / self.sysconf_user_hz as f64;
             menc.write(&[&stat.cpu], idle_s);
+        }
+
+        if self.cpu_usage {
+            let mut cpu_ticks = self.cpu_ticks.lock().unwrap();
+
+            // nothing is emitted for a cpu until it has been seen twice
+            let mut menc = enc.with_info(&metrics.cpu.usage, None);
+            for stat in &stats {
+                let ticks = (stat.busy_ticks, stat.total_ticks);
+                let Some((last_busy, last_total)) = cpu_ticks.insert(stat.cpu.clone(), ticks)
+                else {
+                    continue;
+                };
+
+                let busy = stat.busy_ticks.saturating_sub(last_busy);
+                let total = stat.total_ticks.saturating_sub(last_total);
+                if total > 0 {
+                    menc.write(&[&stat.cpu], busy as f64 / total as f64);
+                }
+            }
 
-            cpus.push(stat.cpu);
+            cpu_ticks.retain(|cpu, _| stats.iter().any(|stat| &stat.cpu == cpu));
         }
 
         let mut menc = enc.with_info(&metrics.cpu.current_frequency, None);
-        for cpu in cpus {
-            let cpufreq = self.parse_cpufreq(&cpu).unwrap_or_default();
-            menc.write(&[&cpu], cpufreq.cur_freq * 1000);
+        for stat in &stats {
+            let cpufreq = self.parse_cpufreq(&stat.cpu).unwrap_or_default();
+            menc.write(&[&stat.cpu], cpufreq.cur_freq * 1000);
         }
 
         Ok(())
     }
 
+    fn collect_clock(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let (synced, offset) = crate::libc::adjtimex_sync()?;
+
+        enc.write(&metrics.cpu.clock_synchronized, synced as u64, None);
+        enc.write(&metrics.cpu.clock_offset, offset, None);
+
+        Ok(())
+    }
+
     fn collect_mem_info(
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
+    ) -> Result<(), collector::CollectorError> {
         let meminfo = self.parse_meminfo().unwrap_or_default();
 
         enc.write(&metrics.mem.size, meminfo.mem_total_kb * 1024, None);
@@ -158,7 +484,7 @@ impl Linux {
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
+    ) -> Result<(), collector::CollectorError> {
         let vmstat = self.parse_vmstat().unwrap_or_default();
 
         enc.write(
@@ -172,13 +498,62 @@ impl Linux {
             None,
         );
 
+        let now = time::Instant::now();
+        let last_pages =
+            self.swap_pages
+                .lock()
+                .unwrap()
+                .replace((now, vmstat.pswpin, vmstat.pswpout));
+
+        // skip the first scrape and counter resets
+        let Some((time, pswpin, pswpout)) = last_pages else {
+            return Ok(());
+        };
+        let secs = now.duration_since(time).as_secs_f64();
+        if secs <= 0.0 || vmstat.pswpin < pswpin || vmstat.pswpout < pswpout {
+            return Ok(());
+        }
+
+        let page_size = self.sysconf_page_size as f64;
+        let in_rate = (vmstat.pswpin - pswpin) as f64 * page_size / secs;
+        let out_rate = (vmstat.pswpout - pswpout) as f64 * page_size / secs;
+        enc.write(&metrics.mem.swap_in_rate, in_rate, None);
+        enc.write(&metrics.mem.swap_out_rate, out_rate, None);
+
+        Ok(())
+    }
+
+    fn collect_mem_zram(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let zrams = self.parse_zram_mm_stat()?;
+
+        let mut menc = enc.with_info(&metrics.mem.zram_compression, None);
+        for zram in &zrams {
+            if zram.compr_data_size > 0 {
+                menc.write(
+                    &[&zram.name],
+                    zram.orig_data_size as f64 / zram.compr_data_size as f64,
+                );
+            }
+        }
+
         Ok(())
     }
 
-    fn collect_fs(&self, metrics: &collector::Metrics, enc: &mut metric::Encoder) -> Result<()> {
+    fn collect_fs(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        // a mount point can be stacked (e.g., tmpfs mounted twice)
+        let mut seen = collections::HashSet::new();
         let mountinfos = self
             .parse_self_mountinfo()?
             .filter_map(|info| info.ok())
+            .filter(|info| seen.insert((info.mount_source.clone(), info.mount_point.clone())))
             .map(|info| {
                 let iostats = self.parse_dev_block(&info.major_minor).unwrap_or_default();
                 (info, iostats)
@@ -208,6 +583,14 @@ impl Linux {
             );
         }
 
+        menc = enc.with_info(&metrics.fs.readonly, None);
+        for (info, _) in mountinfos.iter() {
+            menc.write(
+                &[&info.mount_source, &info.mount_point],
+                info.readonly as u64,
+            );
+        }
+
         Ok(())
     }
 
@@ -215,29 +598,139 @@ impl Linux {
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
-        let zones = self.parse_class_thermal()?;
+    ) -> Result<(), collector::CollectorError> {
+        let zones = self.parse_class_thermal()?.collect::<Result<Vec<_>>>()?;
 
         let mut menc = enc.with_info(&metrics.thermal.temperature, None);
-        for zone in zones {
-            let zone = zone?;
-
+        for zone in &zones {
             menc.write(&[&zone.name], zone.temp as f64 / 1000.0);
         }
 
+        // there is no throttle counter in sysfs; count upward crossings of
+        // the passive trip point between scrapes instead
+        let mut throttles = self.thermal_throttles.lock().unwrap();
+        throttles.retain(|name, _| zones.iter().any(|zone| &zone.name == name));
+
+        let mut menc = enc.with_info(&metrics.thermal.throttle_events, None);
+        for zone in &zones {
+            let Some(trip) = zone.passive_trip else {
+                continue;
+            };
+
+            let (last_temp, count) = throttles.entry(zone.name.clone()).or_insert((zone.temp, 0));
+            if *last_temp < trip && zone.temp >= trip {
+                *count += 1;
+            }
+            *last_temp = zone.temp;
+
+            menc.write(&[&zone.name], *count);
+        }
+
         Ok(())
     }
 
+    fn collect_hwmon(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let Some(hwmons) = self.parse_class_hwmon()? else {
+            return Ok(());
+        };
+        let hwmons = hwmons.collect::<Result<Vec<_>>>()?;
+
+        let mut menc = enc.with_info(&metrics.thermal.fan_pwm, None);
+        for hwmon in &hwmons {
+            for (sensor, pwm) in &hwmon.pwms {
+                menc.write(&[&hwmon.chip, sensor], *pwm as f64 / 255.0);
+            }
+        }
+
+        let mut menc = enc.with_info(&metrics.thermal.disk_temperature, None);
+        for hwmon in &hwmons {
+            if let Some((name, temp)) = &hwmon.disk_temp {
+                menc.write(&[name], *temp as f64 / 1000.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn collect_fs_flash(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        // emmc estimates are upper bounds; 0xb means exceeded
+        let mut wears: Vec<_> = self
+            .parse_emmc_life_time()?
+            .into_iter()
+            .map(|(name, estimate)| (name, estimate as f64 / 10.0))
+            .collect();
+
+        // keep going and report the last error
+        let mut res = Ok(());
+        for name in self.parse_class_nvme()? {
+            match crate::libc::nvme_smart_log(self.devfs_path.join(&name)) {
+                Ok((_, percentage_used)) => wears.push((name, percentage_used as f64 / 100.0)),
+                // the admin command requires CAP_SYS_ADMIN
+                Err(err) => match collector::CollectorError::from(err) {
+                    collector::CollectorError::PermissionDenied(err) => {
+                        log::debug!("skipping {name}: {err:?}")
+                    }
+                    err => res = Err(err),
+                },
+            }
+        }
+
+        let mut menc = enc.with_info(&metrics.fs.flash_wear, None);
+        for (name, wear) in &wears {
+            menc.write(&[name], *wear);
+        }
+
+        let mut menc = enc.with_info(&metrics.fs.flash_life_remaining, None);
+        for (name, wear) in &wears {
+            menc.write(&[name], (1.0 - wear).max(0.0));
+        }
+
+        res
+    }
+
+    fn collect_sysctl(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let mut res = Ok(());
+
+        let mut menc = enc.with_info(&metrics.system.sysctl, None);
+        for key in self.sysctl_keys {
+            match self.parse_sysctl(key) {
+                Ok(Some(val)) => menc.write(&[key], val),
+                Ok(None) => (),
+                // keep going and report the last error
+                Err(err) => res = Err(err.into()),
+            }
+        }
+
+        res
+    }
+
     fn collect_net_link_speed(
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
-        let speeds = self.parse_ethtool()?;
+    ) -> Result<(), collector::CollectorError> {
+        let speeds = self
+            .parse_ethtool()
+            .map_err(collector::CollectorError::netlink)?;
 
         let mut menc = enc.with_info(&metrics.net.link_speed, None);
         for speed in speeds {
-            let speed = speed?;
+            let speed = speed.map_err(collector::CollectorError::netlink)?;
+            if !self.device_matches(&speed.name) {
+                continue;
+            }
 
             menc.write(&[&speed.name], speed.speed as f64 * 1000.0 * 1000.0 / 8.0);
         }
@@ -245,14 +738,146 @@ impl Linux {
         Ok(())
     }
 
+    fn collect_net_link_ring(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let rings = self
+            .parse_ethtool_rings()
+            .map_err(collector::CollectorError::netlink)?
+            .filter(|ring| {
+                ring.as_ref()
+                    .map_or(true, |ring| self.device_matches(&ring.name))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map_err(collector::CollectorError::netlink)?;
+
+        let mut menc = enc.with_info(&metrics.net.link_ring_rx, None);
+        for ring in &rings {
+            menc.write(&[&ring.name], ring.rx);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.link_ring_tx, None);
+        for ring in &rings {
+            menc.write(&[&ring.name], ring.tx);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_link_pause(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let pauses = self
+            .parse_ethtool_pause()
+            .map_err(collector::CollectorError::netlink)?
+            .filter(|pause| {
+                pause
+                    .as_ref()
+                    .map_or(true, |pause| self.device_matches(&pause.name))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map_err(collector::CollectorError::netlink)?;
+
+        let mut menc = enc.with_info(&metrics.net.link_pause_rx, None);
+        for pause in &pauses {
+            menc.write(&[&pause.name], pause.rx as u64);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.link_pause_tx, None);
+        for pause in &pauses {
+            menc.write(&[&pause.name], pause.tx as u64);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_link_eee(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let eees = self
+            .parse_ethtool_eee()
+            .map_err(collector::CollectorError::netlink)?
+            .filter(|eee| {
+                eee.as_ref()
+                    .map_or(true, |eee| self.device_matches(&eee.name))
+            })
+            .collect::<Result<Vec<_>>>()
+            .map_err(collector::CollectorError::netlink)?;
+
+        let mut menc = enc.with_info(&metrics.net.link_eee_active, None);
+        for eee in &eees {
+            menc.write(&[&eee.name], eee.active as u64);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.link_eee_enabled, None);
+        for eee in &eees {
+            menc.write(&[&eee.name], eee.enabled as u64);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_link_tsinfo(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let tsinfos = self
+            .parse_ethtool_tsinfo()
+            .map_err(collector::CollectorError::netlink)?;
+
+        let mut menc = enc.with_info(&metrics.net.link_hw_timestamping, None);
+        for tsinfo in tsinfos {
+            let tsinfo = tsinfo.map_err(collector::CollectorError::netlink)?;
+            if !self.device_matches(&tsinfo.name) {
+                continue;
+            }
+
+            menc.write(&[&tsinfo.name], tsinfo.hw_timestamping as u8);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_link_stat(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let links = self
+            .parse_ethtool_stats()
+            .map_err(collector::CollectorError::netlink)?;
+
+        let mut menc = enc.with_info(&metrics.net.link_stat, None);
+        for link in links {
+            let link = link.map_err(collector::CollectorError::netlink)?;
+            if !self.device_matches(&link.name) {
+                continue;
+            }
+
+            for (stat, val) in link.stats {
+                menc.write(&[&link.name, &stat], val);
+            }
+        }
+
+        Ok(())
+    }
+
     fn collect_net_link_state(
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
+    ) -> Result<(), collector::CollectorError> {
         let links = self
             .parse_links()?
             .filter_map(|link| link.ok())
+            .filter(|link| self.device_matches(&link.name))
             .collect::<Vec<_>>();
 
         let mut menc = enc.with_info(&metrics.net.link_up, None);
@@ -260,12 +885,21 @@ impl Linux {
             menc.write(&[&link.name], link.admin_up as u8);
         }
 
-        menc = enc.with_info(&metrics.net.link_operstate, None);
+        let mut menc = enc.with_info(&metrics.net.link_operstate, None);
         for link in &links {
-            menc.write(&[&link.name], link.operstate);
+            let state = rtnetlink::operstate_name(link.operstate);
+            menc.write(&[&link.name, state], link.operstate);
         }
 
-        menc = enc.with_info(&metrics.net.link_rx, None);
+        // many virtual devices never report an operstate
+        let mut menc = enc.with_info(&metrics.net.link_mismatch, None);
+        for link in &links {
+            let state = rtnetlink::operstate_name(link.operstate);
+            let mismatch = link.admin_up && state != "up" && state != "unknown";
+            menc.write(&[&link.name], mismatch as u8);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.link_rx, None);
         for link in &links {
             menc.write(&[&link.name], link.rx);
         }
@@ -275,21 +909,278 @@ impl Linux {
             menc.write(&[&link.name], link.tx);
         }
 
+        menc = enc.with_info(&metrics.net.link_multicast, None);
+        for link in &links {
+            menc.write(&[&link.name], link.multicast);
+        }
+
+        menc = enc.with_info(&metrics.net.link_collisions, None);
+        for link in &links {
+            menc.write(&[&link.name], link.collisions);
+        }
+
+        // like link_mismatch, an unknown operstate of an admin-up device is
+        // considered up; a link already up when the exporter starts is
+        // reported as up since the first scrape, and a flap between scrapes is
+        // detected by the carrier up count
+        let now = time::SystemTime::now();
+        let mut up_since = self.link_up_since.lock().unwrap();
+        let up_links: Vec<_> = links
+            .iter()
+            .filter(|link| {
+                let state = rtnetlink::operstate_name(link.operstate);
+                link.admin_up && (state == "up" || state == "unknown")
+            })
+            .collect();
+        up_since.retain(|name, _| up_links.iter().any(|link| &link.name == name));
+        let mut menc = enc.with_info(&metrics.net.link_up_since, None);
+        for link in &up_links {
+            let state = (link.index, link.carrier_up_count, now);
+            let since = up_since.entry(link.name.clone()).or_insert(state);
+            if (since.0, since.1) != (link.index, link.carrier_up_count) {
+                *since = state;
+            }
+
+            let secs = since
+                .2
+                .duration_since(time::UNIX_EPOCH)
+                .map_or(0.0, |dur| dur.as_secs_f64());
+            menc.write(&[&link.name], secs);
+        }
+
+        if self.link_rate {
+            self.collect_net_link_rate(metrics, enc, &links);
+        }
+
+        let ppp_links: Vec<_> = links
+            .iter()
+            .filter(|link| link.kind.as_deref() == Some("ppp"))
+            .collect();
+
+        let mut menc = enc.with_info(&metrics.net.ppp_info, None);
+        for link in &ppp_links {
+            menc.write_info(&[&link.name, &link.mtu.to_string()]);
+        }
+
+        // pppd creates a new device for each session, so the session starts
+        // when a device (or a new ifindex for the same name) first shows up
+        let mut sessions = self.ppp_sessions.lock().unwrap();
+        sessions.retain(|name, _| ppp_links.iter().any(|link| &link.name == name));
+        let mut menc = enc.with_info(&metrics.net.ppp_session_uptime, None);
+        for link in &ppp_links {
+            let session = sessions
+                .entry(link.name.clone())
+                .or_insert((link.index, now));
+            if session.0 != link.index {
+                *session = (link.index, now);
+            }
+
+            let uptime = now.duration_since(session.1).unwrap_or_default();
+            menc.write(&[&link.name], uptime.as_secs_f64());
+        }
+
         Ok(())
     }
 
+    fn collect_net_link_rate(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+        links: &[rtnetlink::Link],
+    ) {
+        let now = time::Instant::now();
+        let mut last_bytes = self.link_bytes.lock().unwrap();
+        let last_bytes = mem::replace(
+            &mut *last_bytes,
+            links
+                .iter()
+                .map(|link| (link.name.clone(), (now, link.index, link.rx, link.tx)))
+                .collect(),
+        );
+
+        // skip the first scrape and counter resets, including a device that
+        // is recreated with the same name but a new ifindex
+        let rates: Vec<_> = links
+            .iter()
+            .filter_map(|link| {
+                let (time, index, rx, tx) = last_bytes.get(&link.name)?;
+                let secs = now.duration_since(*time).as_secs_f64();
+                if secs <= 0.0 || link.index != *index || link.rx < *rx || link.tx < *tx {
+                    return None;
+                }
+
+                let rx_rate = (link.rx - rx) as f64 * 8.0 / secs;
+                let tx_rate = (link.tx - tx) as f64 * 8.0 / secs;
+                Some((&link.name, rx_rate, tx_rate))
+            })
+            .collect();
+
+        let mut menc = enc.with_info(&metrics.net.link_rx_rate, None);
+        for (name, rx_rate, _) in &rates {
+            menc.write(&[name], rx_rate);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.link_tx_rate, None);
+        for (name, _, tx_rate) in &rates {
+            menc.write(&[name], tx_rate);
+        }
+    }
+
     fn collect_net_route(
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
-        let routes = self.parse_routes()?;
+    ) -> Result<(), collector::CollectorError> {
+        let routes = self
+            .parse_routes()
+            .map_err(collector::CollectorError::netlink)?;
 
+        let mut present = false;
         let mut menc = enc.with_info(&metrics.net.route_default, None);
         for route in routes {
-            let route = route?;
+            let route = route.map_err(collector::CollectorError::netlink)?;
+
+            menc.write_info(&[&route.ip().to_string()]);
+            present = true;
+        }
+
+        enc.write(&metrics.net.default_route_present, present as u64, None);
 
-            menc.write(&[&route.ip().to_string()], 1);
+        let counts = self
+            .parse_route_count()
+            .map_err(collector::CollectorError::netlink)?;
+
+        let mut menc = enc.with_info(&metrics.net.route_count, None);
+        for ((table, family), count) in counts {
+            let table = rtnetlink::route_table_name(table);
+            let family = rtnetlink::route_family_name(family);
+            menc.write(&[&table, family], count);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_snmp(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let snmp = self.parse_net_snmp()?;
+
+        enc.write(&metrics.net.tcp_out_segments, snmp.tcp_out_segs, None);
+        enc.write(
+            &metrics.net.tcp_retransmitted_segments,
+            snmp.tcp_retrans_segs,
+            None,
+        );
+
+        let last_segs = self
+            .tcp_segs
+            .lock()
+            .unwrap()
+            .replace((snmp.tcp_out_segs, snmp.tcp_retrans_segs));
+
+        // skip the first scrape, counter resets, and idle periods
+        let Some((out_segs, retrans_segs)) = last_segs else {
+            return Ok(());
+        };
+        if snmp.tcp_out_segs <= out_segs || snmp.tcp_retrans_segs < retrans_segs {
+            return Ok(());
+        }
+
+        let ratio =
+            (snmp.tcp_retrans_segs - retrans_segs) as f64 / (snmp.tcp_out_segs - out_segs) as f64;
+        enc.write(&metrics.net.tcp_retransmit_ratio, ratio, None);
+
+        Ok(())
+    }
+
+    fn collect_net_snmp6(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let snmp6 = self.parse_net_snmp6()?;
+
+        enc.write(&metrics.net.ip6_in_receives, snmp6.ip6_in_receives, None);
+        enc.write(&metrics.net.ip6_in_discards, snmp6.ip6_in_discards, None);
+        enc.write(&metrics.net.ip6_reasm_fails, snmp6.ip6_reasm_fails, None);
+        enc.write(&metrics.net.icmp6_in_errors, snmp6.icmp6_in_errors, None);
+        enc.write(
+            &metrics.net.icmp6_in_router_advertisements,
+            snmp6.icmp6_in_router_advertisements,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn collect_net_tcp(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let counts = self.parse_net_tcp()?;
+
+        let mut menc = enc.with_info(&metrics.net.tcp_connections, None);
+        for (state, count) in iter::zip(procfs::TCP_STATES, counts).skip(1) {
+            menc.write(&[state], count);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_listen(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let ports = self.parse_net_listen()?;
+
+        let mut menc = enc.with_info(&metrics.net.listen_port_info, None);
+        for port in &ports {
+            menc.write_info(&[port.proto, &port.port.to_string(), &port.addr.to_string()]);
+        }
+
+        Ok(())
+    }
+
+    fn collect_net_ephemeral_ports(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let ports = self.parse_net_ephemeral_ports()?;
+
+        enc.write(&metrics.net.ephemeral_ports, ports.total, None);
+
+        let mut menc = enc.with_info(&metrics.net.ephemeral_ports_used, None);
+        menc.write(&["tcp"], ports.tcp_used);
+        menc.write(&["udp"], ports.udp_used);
+
+        Ok(())
+    }
+
+    fn collect_net_softnet(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let softnets = self.parse_net_softnet()?;
+
+        let mut menc = enc.with_info(&metrics.net.softnet_processed, None);
+        for softnet in &softnets {
+            menc.write(&[&softnet.cpu], softnet.processed);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.softnet_dropped, None);
+        for softnet in &softnets {
+            menc.write(&[&softnet.cpu], softnet.dropped);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.softnet_time_squeezed, None);
+        for softnet in &softnets {
+            menc.write(&[&softnet.cpu], softnet.time_squeeze);
         }
 
         Ok(())
@@ -299,26 +1190,52 @@ impl Linux {
         &self,
         metrics: &collector::Metrics,
         enc: &mut metric::Encoder,
-    ) -> Result<()> {
-        let sets = self.parse_nfnetlink()?;
+    ) -> Result<(), collector::CollectorError> {
+        let sets = self
+            .parse_nfnetlink()
+            .map_err(collector::CollectorError::netlink)?;
 
         let mut menc = enc.with_info(&metrics.net.nft_set_counter, None);
         for set in sets {
-            let set = set?;
-            let counters = self.parse_nft_set(&set)?;
-            for counter in counters {
-                let counter = counter?;
+            let set = set.map_err(collector::CollectorError::netlink)?;
+            let counters = self
+                .parse_nft_set(&set)
+                .map_err(collector::CollectorError::netlink)?;
+            write_nft_set_counters(&mut menc, &set, counters)?;
+        }
 
-                menc.write(
-                    &[
-                        &set.family.to_string(),
-                        &set.table,
-                        &set.name,
-                        &counter.addr,
-                    ],
-                    counter.bytes,
-                );
-            }
+        // chains without rules are reported as well
+        let mut rules = collections::BTreeMap::new();
+        for chain in self
+            .parse_nft_chains()
+            .map_err(collector::CollectorError::netlink)?
+        {
+            let chain = chain.map_err(collector::CollectorError::netlink)?;
+            rules.insert((chain.family, chain.table, chain.name), 0);
+        }
+        for chain in self
+            .parse_nft_rules()
+            .map_err(collector::CollectorError::netlink)?
+        {
+            let chain = chain.map_err(collector::CollectorError::netlink)?;
+            *rules
+                .entry((chain.family, chain.table, chain.name))
+                .or_default() += 1;
+        }
+
+        let mut chains = collections::BTreeMap::<_, u64>::new();
+        for (family, table, _) in rules.keys() {
+            *chains.entry((*family, table)).or_default() += 1;
+        }
+
+        let mut menc = enc.with_info(&metrics.net.nft_chains, None);
+        for ((family, table), count) in chains {
+            menc.write(&[nfnetlink::nft_family_name(family), table], count);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.nft_rules, None);
+        for ((family, table, chain), count) in &rules {
+            menc.write(&[nfnetlink::nft_family_name(*family), table, chain], count);
         }
 
         Ok(())
@@ -341,3 +1258,109 @@ impl Linux {
         fs::read_dir(&path).with_context(|| format!("failed to open {:?}", path))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect(
+        lin: &Linux,
+        f: impl FnOnce(
+            &Linux,
+            &collector::Metrics,
+            &mut metric::Encoder,
+        ) -> Result<(), collector::CollectorError>,
+    ) -> String {
+        collector::render(|metrics, enc| assert!(f(lin, metrics, enc).is_ok()))
+    }
+
+    #[test]
+    fn cpu_offline() {
+        let mut lin = Linux::with_fixtures("procfs", "sysfs");
+
+        // usage needs two scrapes
+        let text = collect(&lin, Linux::collect_cpu);
+        assert!(!text.contains("test_cpu_usage_ratio{"));
+        assert!(lin.cpu_ticks.lock().unwrap().contains_key("cpu1"));
+
+        lin.procfs_path = Linux::with_fixtures("procfs-cpu1-offline", "sysfs").procfs_path;
+        let text = collect(&lin, Linux::collect_cpu);
+        assert!(text.contains("test_cpu_usage_ratio{cpu=\"cpu0\"} 0.375\n"));
+        assert!(!text.contains("cpu=\"cpu1\""));
+        assert!(!lin.cpu_ticks.lock().unwrap().contains_key("cpu1"));
+
+        // a cpu coming back online starts over
+        lin.procfs_path = Linux::with_fixtures("procfs", "sysfs").procfs_path;
+        let text = collect(&lin, Linux::collect_cpu);
+        assert!(!text.contains("test_cpu_usage_ratio{cpu=\"cpu1\"}"));
+        assert!(text.contains("test_cpu_idle_seconds_total{cpu=\"cpu1\"}"));
+    }
+
+    #[test]
+    fn link_rate_ifindex_change() {
+        let lin = Linux::with_fixtures("procfs", "sysfs");
+        let link = |index, rx, tx| rtnetlink::Link {
+            index,
+            name: "wg0".to_string(),
+            kind: Some("wireguard".to_string()),
+            mtu: 1420,
+            admin_up: true,
+            operstate: 0,
+            carrier_up_count: 1,
+            rx,
+            tx,
+            multicast: 0,
+            collisions: 0,
+        };
+        // pretends the last scrape was a second ago and returns the rx rate
+        let rx_rate = |link: rtnetlink::Link| {
+            let last = time::Instant::now() - time::Duration::from_secs(1);
+            for (time, _, _, _) in lin.link_bytes.lock().unwrap().values_mut() {
+                *time = last;
+            }
+            let text =
+                collector::render(|metrics, enc| lin.collect_net_link_rate(metrics, enc, &[link]));
+            text.lines().find_map(|line| {
+                let val =
+                    line.strip_prefix("test_network_link_rx_bits_per_second{device=\"wg0\"} ")?;
+                val.parse::<f64>().ok()
+            })
+        };
+        let is_about = |rate: Option<f64>, expected: f64| {
+            rate.is_some_and(|rate| (rate - expected).abs() < expected * 0.01)
+        };
+
+        // the first scrape has nothing to compare against
+        assert_eq!(rx_rate(link(5, 1000, 2000)), None);
+        assert!(is_about(rx_rate(link(5, 2000, 4000)), 8000.0));
+
+        // recreated with more bytes than before but a new ifindex
+        assert_eq!(rx_rate(link(6, 5000, 9000)), None);
+        assert!(is_about(rx_rate(link(6, 6000, 10000)), 8000.0));
+
+        // a counter reset without an ifindex change
+        assert_eq!(rx_rate(link(6, 100, 100)), None);
+    }
+
+    #[test]
+    fn nft_set_counter_labels() {
+        let (set, counters) = nfnetlink::set_fixture(&[
+            ([10, 0, 0, 1, 0x01, 0xbb, 0, 0], 1234),
+            ([192, 168, 1, 2, 0x00, 0x16, 0, 0], 56),
+        ]);
+        let text = collector::render(|metrics, enc| {
+            let mut menc = enc.with_info(&metrics.net.nft_set_counter, None);
+            write_nft_set_counters(&mut menc, &set, counters).unwrap();
+        });
+
+        // addr is formatted from the element key
+        assert!(text.contains(
+            "test_network_nft_set_counter_bytes_total\
+             {family=\"inet\",table=\"filter\",set=\"allow\",addr=\"10.0.0.1 . 443\"} 1234\n"
+        ));
+        assert!(text.contains(
+            "test_network_nft_set_counter_bytes_total\
+             {family=\"inet\",table=\"filter\",set=\"allow\",addr=\"192.168.1.2 . 22\"} 56\n"
+        ));
+    }
+}
@@ -1,21 +1,33 @@
 // Copyright 2025 Google LLC
 // SPDX-License-Identifier: MIT
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use neli::{
+    Size, ToBytes,
     attr::Attribute,
-    consts::nl::NlmF,
-    genl::{GenlAttrHandle, Genlmsghdr, GenlmsghdrBuilder, NoUserHeader},
+    consts::{genl::NlAttrType, nl::NlmF},
+    genl::{
+        AttrTypeBuilder, GenlAttrHandle, Genlmsghdr, GenlmsghdrBuilder, Nlattr, NlattrBuilder,
+        NoUserHeader,
+    },
     nl::NlPayload,
-    router::synchronous::NlRouterReceiverHandle,
+    router::synchronous::{NlRouter, NlRouterReceiverHandle},
+    types::{Buffer, GenlBuffer},
 };
+use std::collections;
 
 pub(super) const ETHTOOL_GENL_NAME: &str = "ethtool";
 const ETHTOOL_GENL_VERSION: u8 = 1;
 
 #[neli::neli_enum(serialized_type = "u8")]
 enum EthtoolMsg {
+    StrsetGet = 1,
     LinkModesGet = 4,
+    RingsGet = 15,
+    PauseGet = 21,
+    EeeGet = 23,
+    TsinfoGet = 25,
+    StatsGet = 32,
 }
 impl neli::consts::genl::Cmd for EthtoolMsg {}
 
@@ -26,21 +38,164 @@ enum EthtoolAttrLinkModes {
 }
 impl neli::consts::genl::NlAttrType for EthtoolAttrLinkModes {}
 
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrRings {
+    Header = 1,
+    Rx = 6,
+    Tx = 9,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrRings {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrPause {
+    Header = 1,
+    Rx = 3,
+    Tx = 4,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrPause {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrEee {
+    Header = 1,
+    Active = 4,
+    Enabled = 5,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrEee {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrTsinfo {
+    Header = 1,
+    Timestamping = 2,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrTsinfo {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrStrset {
+    Header = 1,
+    Stringsets = 2,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrStrset {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrStringsets {
+    Stringset = 1,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrStringsets {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrStringset {
+    Id = 1,
+    Strings = 3,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrStringset {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrStrings {
+    String = 1,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrStrings {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrString {
+    Index = 1,
+    Value = 2,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrString {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrStats {
+    Header = 2,
+    Groups = 3,
+    Grp = 4,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrStats {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrStatsGrp {
+    SsId = 3,
+    Stat = 4,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrStatsGrp {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum EthtoolAttrBitset {
+    Nomask = 1,
+    Size = 2,
+    Value = 4,
+}
+impl neli::consts::genl::NlAttrType for EthtoolAttrBitset {}
+
 #[neli::neli_enum(serialized_type = "u16")]
 enum EthtoolAttrHeader {
     DevName = 2,
+    Flags = 3,
 }
 impl neli::consts::genl::NlAttrType for EthtoolAttrHeader {}
 
-type Ethtoolmsghdr = Genlmsghdr<EthtoolMsg, EthtoolAttrLinkModes>;
-type EthtoolmsghdrBuilder = GenlmsghdrBuilder<EthtoolMsg, EthtoolAttrLinkModes, NoUserHeader>;
-type EthtoolReceiverHandle = NlRouterReceiverHandle<u16, Ethtoolmsghdr>;
+type Ethtoolmsghdr<T> = Genlmsghdr<EthtoolMsg, T>;
+type EthtoolmsghdrBuilder<T> = GenlmsghdrBuilder<EthtoolMsg, T, NoUserHeader>;
+type EthtoolReceiverHandle<T> = NlRouterReceiverHandle<u16, Ethtoolmsghdr<T>>;
+
+// ETH_SS_STATS_* string sets of the standard stats groups
+const ETH_SS_STATS_ETH_PHY: u32 = 17;
+const ETH_SS_STATS_ETH_MAC: u32 = 18;
+const ETH_SS_STATS_ETH_CTRL: u32 = 19;
+const ETH_SS_STATS_RMON: u32 = 20;
+
+// ETHTOOL_STATS_ETH_PHY, _ETH_MAC, _ETH_CTRL, and _RMON
+const ETHTOOL_STATS_GROUP_COUNT: u32 = 4;
+
+const ETHTOOL_FLAG_COMPACT_BITSETS: u32 = 1;
+
+// SOF_TIMESTAMPING_TX_HARDWARE and SOF_TIMESTAMPING_RX_HARDWARE
+const SOF_TIMESTAMPING_HARDWARE: u32 = (1 << 0) | (1 << 2);
+
+fn build_attr<T, P>(ty: T, payload: P) -> Result<Nlattr<T, Buffer>>
+where
+    T: NlAttrType,
+    P: Size + ToBytes,
+{
+    let attr = NlattrBuilder::default()
+        .nla_type(AttrTypeBuilder::default().nla_type(ty).build()?)
+        .nla_payload(payload)
+        .build()?;
+
+    Ok(attr)
+}
 
 pub(super) struct LinkSpeed {
     pub name: String,
     pub speed: i32,
 }
 
+pub(super) struct LinkRings {
+    pub name: String,
+    pub rx: u32,
+    pub tx: u32,
+}
+
+pub(super) struct LinkPause {
+    pub name: String,
+    pub rx: bool,
+    pub tx: bool,
+}
+
+pub(super) struct LinkEee {
+    pub name: String,
+    pub active: bool,
+    pub enabled: bool,
+}
+
+pub(super) struct LinkTsinfo {
+    pub name: String,
+    pub hw_timestamping: bool,
+}
+
+pub(super) struct LinkStats {
+    pub name: String,
+    pub stats: Vec<(String, u64)>,
+}
+
 fn parse_header_attrs(header: GenlAttrHandle<EthtoolAttrHeader>) -> Option<String> {
     for attr in header.iter() {
         if attr.nla_type().nla_type() == &EthtoolAttrHeader::DevName {
@@ -51,7 +206,7 @@ fn parse_header_attrs(header: GenlAttrHandle<EthtoolAttrHeader>) -> Option<Strin
     None
 }
 
-fn parse_link_modes_get_response(resp: &Ethtoolmsghdr) -> Option<LinkSpeed> {
+fn parse_link_modes_get_response(resp: &Ethtoolmsghdr<EthtoolAttrLinkModes>) -> Option<LinkSpeed> {
     let mut name = None;
     let mut speed = None;
     for attr in resp.attrs().iter() {
@@ -76,7 +231,7 @@ fn parse_link_modes_get_response(resp: &Ethtoolmsghdr) -> Option<LinkSpeed> {
 }
 
 pub(super) struct EthtoolIter {
-    recv: EthtoolReceiverHandle,
+    recv: EthtoolReceiverHandle<EthtoolAttrLinkModes>,
 }
 
 impl Iterator for EthtoolIter {
@@ -84,7 +239,10 @@ impl Iterator for EthtoolIter {
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let genlmsg = match self.recv.next_typed::<u16, Ethtoolmsghdr>() {
+            let genlmsg = match self
+                .recv
+                .next_typed::<u16, Ethtoolmsghdr<EthtoolAttrLinkModes>>()
+            {
                 Some(Ok(msg)) => msg,
                 Some(Err(err)) => return Some(Err(err).context("failed to recv from ethtool")),
                 None => return None,
@@ -100,17 +258,532 @@ impl Iterator for EthtoolIter {
     }
 }
 
+fn parse_rings_get_response(resp: &Ethtoolmsghdr<EthtoolAttrRings>) -> Option<LinkRings> {
+    let mut name = None;
+    let mut rx = None;
+    let mut tx = None;
+    for attr in resp.attrs().iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrRings::Header => {
+                name = attr
+                    .get_attr_handle::<EthtoolAttrHeader>()
+                    .ok()
+                    .and_then(parse_header_attrs);
+            }
+            EthtoolAttrRings::Rx => {
+                rx = attr.get_payload_as::<u32>().ok();
+            }
+            EthtoolAttrRings::Tx => {
+                tx = attr.get_payload_as::<u32>().ok();
+            }
+            _ => (),
+        }
+    }
+
+    name.map(|name| LinkRings {
+        name,
+        rx: rx.unwrap_or(0),
+        tx: tx.unwrap_or(0),
+    })
+}
+
+pub(super) struct EthtoolRingsIter {
+    recv: EthtoolReceiverHandle<EthtoolAttrRings>,
+}
+
+impl Iterator for EthtoolRingsIter {
+    type Item = Result<LinkRings>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let genlmsg = match self
+                .recv
+                .next_typed::<u16, Ethtoolmsghdr<EthtoolAttrRings>>()
+            {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => return Some(Err(err).context("failed to recv from ethtool")),
+                None => return None,
+            };
+
+            if let Some(rings) = genlmsg.get_payload().and_then(parse_rings_get_response) {
+                return Some(Ok(rings));
+            }
+        }
+    }
+}
+
+fn parse_pause_get_response(resp: &Ethtoolmsghdr<EthtoolAttrPause>) -> Option<LinkPause> {
+    let mut name = None;
+    let mut rx = None;
+    let mut tx = None;
+    for attr in resp.attrs().iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrPause::Header => {
+                name = attr
+                    .get_attr_handle::<EthtoolAttrHeader>()
+                    .ok()
+                    .and_then(parse_header_attrs);
+            }
+            EthtoolAttrPause::Rx => {
+                rx = attr.get_payload_as::<u8>().ok();
+            }
+            EthtoolAttrPause::Tx => {
+                tx = attr.get_payload_as::<u8>().ok();
+            }
+            _ => (),
+        }
+    }
+
+    name.map(|name| LinkPause {
+        name,
+        rx: rx.unwrap_or(0) != 0,
+        tx: tx.unwrap_or(0) != 0,
+    })
+}
+
+pub(super) struct EthtoolPauseIter {
+    recv: EthtoolReceiverHandle<EthtoolAttrPause>,
+}
+
+impl Iterator for EthtoolPauseIter {
+    type Item = Result<LinkPause>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let genlmsg = match self
+                .recv
+                .next_typed::<u16, Ethtoolmsghdr<EthtoolAttrPause>>()
+            {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => return Some(Err(err).context("failed to recv from ethtool")),
+                None => return None,
+            };
+
+            if let Some(pause) = genlmsg.get_payload().and_then(parse_pause_get_response) {
+                return Some(Ok(pause));
+            }
+        }
+    }
+}
+
+fn parse_eee_get_response(resp: &Ethtoolmsghdr<EthtoolAttrEee>) -> Option<LinkEee> {
+    let mut name = None;
+    let mut active = None;
+    let mut enabled = None;
+    for attr in resp.attrs().iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrEee::Header => {
+                name = attr
+                    .get_attr_handle::<EthtoolAttrHeader>()
+                    .ok()
+                    .and_then(parse_header_attrs);
+            }
+            EthtoolAttrEee::Active => {
+                active = attr.get_payload_as::<u8>().ok();
+            }
+            EthtoolAttrEee::Enabled => {
+                enabled = attr.get_payload_as::<u8>().ok();
+            }
+            _ => (),
+        }
+    }
+
+    name.map(|name| LinkEee {
+        name,
+        active: active.unwrap_or(0) != 0,
+        enabled: enabled.unwrap_or(0) != 0,
+    })
+}
+
+pub(super) struct EthtoolEeeIter {
+    recv: EthtoolReceiverHandle<EthtoolAttrEee>,
+}
+
+impl Iterator for EthtoolEeeIter {
+    type Item = Result<LinkEee>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let genlmsg = match self.recv.next_typed::<u16, Ethtoolmsghdr<EthtoolAttrEee>>() {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => return Some(Err(err).context("failed to recv from ethtool")),
+                None => return None,
+            };
+
+            if let Some(eee) = genlmsg.get_payload().and_then(parse_eee_get_response) {
+                return Some(Ok(eee));
+            }
+        }
+    }
+}
+
+fn parse_compact_bitset(bitset: GenlAttrHandle<EthtoolAttrBitset>) -> Option<u32> {
+    // only the first word is needed
+    let value = bitset.get_attribute(EthtoolAttrBitset::Value)?;
+    let word = value.payload().as_ref().get(..4)?;
+    Some(u32::from_ne_bytes(word.try_into().ok()?))
+}
+
+fn parse_tsinfo_get_response(resp: &Ethtoolmsghdr<EthtoolAttrTsinfo>) -> Option<LinkTsinfo> {
+    let mut name = None;
+    let mut timestamping = None;
+    for attr in resp.attrs().iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrTsinfo::Header => {
+                name = attr
+                    .get_attr_handle::<EthtoolAttrHeader>()
+                    .ok()
+                    .and_then(parse_header_attrs);
+            }
+            EthtoolAttrTsinfo::Timestamping => {
+                timestamping = attr
+                    .get_attr_handle::<EthtoolAttrBitset>()
+                    .ok()
+                    .and_then(parse_compact_bitset);
+            }
+            _ => (),
+        }
+    }
+
+    name.map(|name| LinkTsinfo {
+        name,
+        hw_timestamping: timestamping.unwrap_or(0) & SOF_TIMESTAMPING_HARDWARE
+            == SOF_TIMESTAMPING_HARDWARE,
+    })
+}
+
+pub(super) struct EthtoolTsinfoIter {
+    recv: EthtoolReceiverHandle<EthtoolAttrTsinfo>,
+}
+
+impl Iterator for EthtoolTsinfoIter {
+    type Item = Result<LinkTsinfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let genlmsg = match self
+                .recv
+                .next_typed::<u16, Ethtoolmsghdr<EthtoolAttrTsinfo>>()
+            {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => return Some(Err(err).context("failed to recv from ethtool")),
+                None => return None,
+            };
+
+            if let Some(tsinfo) = genlmsg.get_payload().and_then(parse_tsinfo_get_response) {
+                return Some(Ok(tsinfo));
+            }
+        }
+    }
+}
+
+// string set id to strings
+type StringSets = collections::HashMap<u32, Vec<String>>;
+
+fn parse_stringset(set: GenlAttrHandle<EthtoolAttrStringset>) -> Option<(u32, Vec<String>)> {
+    let mut id = None;
+    let mut strings = Vec::new();
+    for attr in set.iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrStringset::Id => {
+                id = attr.get_payload_as::<u32>().ok();
+            }
+            EthtoolAttrStringset::Strings => {
+                let Ok(handle) = attr.get_attr_handle::<EthtoolAttrStrings>() else {
+                    continue;
+                };
+
+                for string in handle.iter() {
+                    let Ok(string) = string.get_attr_handle::<EthtoolAttrString>() else {
+                        continue;
+                    };
+                    let index = string.get_attr_payload_as::<u32>(EthtoolAttrString::Index);
+                    let value =
+                        string.get_attr_payload_as_with_len::<String>(EthtoolAttrString::Value);
+                    if let (Ok(index), Ok(value)) = (index, value) {
+                        let index = index as usize;
+                        if strings.len() <= index {
+                            strings.resize(index + 1, String::new());
+                        }
+                        strings[index] = value;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    id.map(|id| (id, strings))
+}
+
+fn parse_strset_get_response(resp: &Ethtoolmsghdr<EthtoolAttrStrset>) -> StringSets {
+    let mut string_sets = StringSets::new();
+    for attr in resp.attrs().iter() {
+        if attr.nla_type().nla_type() != &EthtoolAttrStrset::Stringsets {
+            continue;
+        }
+        let Ok(sets) = attr.get_attr_handle::<EthtoolAttrStringsets>() else {
+            continue;
+        };
+
+        string_sets.extend(sets.iter().filter_map(|set| {
+            set.get_attr_handle::<EthtoolAttrStringset>()
+                .ok()
+                .and_then(parse_stringset)
+        }));
+    }
+
+    string_sets
+}
+
+fn parse_stats_grp(
+    grp: GenlAttrHandle<EthtoolAttrStatsGrp>,
+    string_sets: &StringSets,
+) -> Vec<(String, u64)> {
+    let mut strings = None;
+    let mut vals = Vec::new();
+    for attr in grp.iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrStatsGrp::SsId => {
+                strings = attr
+                    .get_payload_as::<u32>()
+                    .ok()
+                    .and_then(|id| string_sets.get(&id));
+            }
+            EthtoolAttrStatsGrp::Stat => {
+                // a single attr whose type is the index into the string set
+                let Ok(stat) = attr.get_attr_handle::<u16>() else {
+                    continue;
+                };
+                for stat in stat.iter() {
+                    if let Ok(val) = stat.get_payload_as::<u64>() {
+                        vals.push((*stat.nla_type().nla_type(), val));
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let Some(strings) = strings else {
+        return Vec::new();
+    };
+
+    vals.into_iter()
+        .filter_map(|(idx, val)| {
+            strings
+                .get(idx as usize)
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.clone(), val))
+        })
+        .collect()
+}
+
+fn parse_stats_get_response(
+    resp: &Ethtoolmsghdr<EthtoolAttrStats>,
+    string_sets: &StringSets,
+) -> Option<LinkStats> {
+    let mut name = None;
+    let mut stats = Vec::new();
+    for attr in resp.attrs().iter() {
+        match attr.nla_type().nla_type() {
+            EthtoolAttrStats::Header => {
+                name = attr
+                    .get_attr_handle::<EthtoolAttrHeader>()
+                    .ok()
+                    .and_then(parse_header_attrs);
+            }
+            EthtoolAttrStats::Grp => {
+                if let Ok(grp) = attr.get_attr_handle::<EthtoolAttrStatsGrp>() {
+                    stats.extend(parse_stats_grp(grp, string_sets));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    match name {
+        Some(name) if !stats.is_empty() => Some(LinkStats { name, stats }),
+        _ => None,
+    }
+}
+
+pub(super) struct EthtoolStatsIter {
+    recv: EthtoolReceiverHandle<EthtoolAttrStats>,
+    string_sets: StringSets,
+}
+
+impl Iterator for EthtoolStatsIter {
+    type Item = Result<LinkStats>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let genlmsg = match self
+                .recv
+                .next_typed::<u16, Ethtoolmsghdr<EthtoolAttrStats>>()
+            {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => return Some(Err(err).context("failed to recv from ethtool")),
+                None => return None,
+            };
+
+            if let Some(stats) = genlmsg
+                .get_payload()
+                .and_then(|resp| parse_stats_get_response(resp, &self.string_sets))
+            {
+                return Some(Ok(stats));
+            }
+        }
+    }
+}
+
 impl super::Linux {
+    fn genl_sock(&self) -> Result<&NlRouter> {
+        self.genl_sock
+            .as_ref()
+            .context("generic netlink socket is unavailable")
+    }
+
+    fn ethtool_id(&self) -> Result<u16> {
+        self.ethtool_id
+            .ok_or_else(|| anyhow!("ethtool genl family is unavailable"))
+    }
+
     pub(super) fn parse_ethtool(&self) -> Result<EthtoolIter> {
-        let req = EthtoolmsghdrBuilder::default()
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrLinkModes>::default()
             .cmd(EthtoolMsg::LinkModesGet)
             .version(ETHTOOL_GENL_VERSION)
             .build()?;
-        let recv: EthtoolReceiverHandle = self
-            .genl_sock
-            .send(self.ethtool_id, NlmF::DUMP, NlPayload::Payload(req))
+        let recv: EthtoolReceiverHandle<EthtoolAttrLinkModes> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::DUMP, NlPayload::Payload(req))
             .context("failed to send to ethtool")?;
 
         Ok(EthtoolIter { recv })
     }
+
+    pub(super) fn parse_ethtool_rings(&self) -> Result<EthtoolRingsIter> {
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrRings>::default()
+            .cmd(EthtoolMsg::RingsGet)
+            .version(ETHTOOL_GENL_VERSION)
+            .build()?;
+        let recv: EthtoolReceiverHandle<EthtoolAttrRings> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to ethtool")?;
+
+        Ok(EthtoolRingsIter { recv })
+    }
+
+    pub(super) fn parse_ethtool_pause(&self) -> Result<EthtoolPauseIter> {
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrPause>::default()
+            .cmd(EthtoolMsg::PauseGet)
+            .version(ETHTOOL_GENL_VERSION)
+            .build()?;
+        let recv: EthtoolReceiverHandle<EthtoolAttrPause> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to ethtool")?;
+
+        Ok(EthtoolPauseIter { recv })
+    }
+
+    pub(super) fn parse_ethtool_eee(&self) -> Result<EthtoolEeeIter> {
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrEee>::default()
+            .cmd(EthtoolMsg::EeeGet)
+            .version(ETHTOOL_GENL_VERSION)
+            .build()?;
+        let recv: EthtoolReceiverHandle<EthtoolAttrEee> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to ethtool")?;
+
+        Ok(EthtoolEeeIter { recv })
+    }
+
+    pub(super) fn parse_ethtool_tsinfo(&self) -> Result<EthtoolTsinfoIter> {
+        let header = build_attr(EthtoolAttrTsinfo::Header, Vec::<u8>::new())?.nest(&build_attr(
+            EthtoolAttrHeader::Flags,
+            ETHTOOL_FLAG_COMPACT_BITSETS,
+        )?)?;
+
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrTsinfo>::default()
+            .cmd(EthtoolMsg::TsinfoGet)
+            .version(ETHTOOL_GENL_VERSION)
+            .attrs([header].into_iter().collect::<GenlBuffer<_, _>>())
+            .build()?;
+        let recv: EthtoolReceiverHandle<EthtoolAttrTsinfo> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to ethtool")?;
+
+        Ok(EthtoolTsinfoIter { recv })
+    }
+
+    fn parse_ethtool_string_sets(&self, ids: &[u32]) -> Result<StringSets> {
+        // the header is required even without a device
+        let header = build_attr(EthtoolAttrStrset::Header, Vec::<u8>::new())?
+            .nest(&build_attr(EthtoolAttrHeader::Flags, 0u32)?)?;
+
+        let mut sets = build_attr(EthtoolAttrStrset::Stringsets, Vec::<u8>::new())?;
+        for &id in ids {
+            let set = build_attr(EthtoolAttrStringsets::Stringset, Vec::<u8>::new())?
+                .nest(&build_attr(EthtoolAttrStringset::Id, id)?)?;
+            sets = sets.nest(&set)?;
+        }
+
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrStrset>::default()
+            .cmd(EthtoolMsg::StrsetGet)
+            .version(ETHTOOL_GENL_VERSION)
+            .attrs([header, sets].into_iter().collect::<GenlBuffer<_, _>>())
+            .build()?;
+        let mut recv: EthtoolReceiverHandle<EthtoolAttrStrset> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::empty(), NlPayload::Payload(req))
+            .context("failed to send to ethtool")?;
+
+        let mut string_sets = StringSets::new();
+        while let Some(genlmsg) = recv.next_typed::<u16, Ethtoolmsghdr<EthtoolAttrStrset>>() {
+            let genlmsg = genlmsg.context("failed to recv from ethtool")?;
+            if let Some(resp) = genlmsg.get_payload() {
+                string_sets.extend(parse_strset_get_response(resp));
+            }
+        }
+
+        Ok(string_sets)
+    }
+
+    pub(super) fn parse_ethtool_stats(&self) -> Result<EthtoolStatsIter> {
+        // stat names are global and are resolved by string set ids
+        let string_sets = self.parse_ethtool_string_sets(&[
+            ETH_SS_STATS_ETH_PHY,
+            ETH_SS_STATS_ETH_MAC,
+            ETH_SS_STATS_ETH_CTRL,
+            ETH_SS_STATS_RMON,
+        ])?;
+
+        // request all groups as a compact bitset
+        let groups = build_attr(EthtoolAttrStats::Groups, Vec::<u8>::new())?
+            .nest(&build_attr(EthtoolAttrBitset::Nomask, Vec::<u8>::new())?)?
+            .nest(&build_attr(
+                EthtoolAttrBitset::Size,
+                ETHTOOL_STATS_GROUP_COUNT,
+            )?)?
+            .nest(&build_attr(
+                EthtoolAttrBitset::Value,
+                (1u32 << ETHTOOL_STATS_GROUP_COUNT) - 1,
+            )?)?;
+
+        let req = EthtoolmsghdrBuilder::<EthtoolAttrStats>::default()
+            .cmd(EthtoolMsg::StatsGet)
+            .version(ETHTOOL_GENL_VERSION)
+            .attrs([groups].into_iter().collect::<GenlBuffer<_, _>>())
+            .build()?;
+        let recv: EthtoolReceiverHandle<EthtoolAttrStats> = self
+            .genl_sock()?
+            .send(self.ethtool_id()?, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to ethtool")?;
+
+        Ok(EthtoolStatsIter { recv, string_sets })
+    }
 }
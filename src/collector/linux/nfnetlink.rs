@@ -10,7 +10,7 @@ use neli::{
     err::RouterError,
     genl::{AttrTypeBuilder, GenlAttrHandle, NlattrBuilder},
     nl::{NlPayload, Nlmsghdr},
-    router::synchronous::NlRouterReceiverHandle,
+    router::synchronous::{NlRouter, NlRouterReceiverHandle},
     types::{Buffer, GenlBuffer},
 };
 use std::{io, net};
@@ -31,6 +31,8 @@ struct Nfgenmsg<T> {
 
 #[neli::neli_enum(serialized_type = "u16")]
 enum NftMsg {
+    Getchain = ((NFNL_SUBSYS_NFTABLES as u16) << 8) | 4,
+    Getrule = ((NFNL_SUBSYS_NFTABLES as u16) << 8) | 7,
     Getset = ((NFNL_SUBSYS_NFTABLES as u16) << 8) | 10,
     Getsetelem = ((NFNL_SUBSYS_NFTABLES as u16) << 8) | 13,
 }
@@ -42,6 +44,20 @@ enum NftaList {
 }
 impl NlAttrType for NftaList {}
 
+#[neli::neli_enum(serialized_type = "u16")]
+enum NftaChain {
+    Table = 1,
+    Name = 3,
+}
+impl NlAttrType for NftaChain {}
+
+#[neli::neli_enum(serialized_type = "u16")]
+enum NftaRule {
+    Table = 1,
+    Chain = 2,
+}
+impl NlAttrType for NftaRule {}
+
 #[neli::neli_enum(serialized_type = "u16")]
 enum NftaSet {
     Table = 1,
@@ -54,7 +70,9 @@ impl NlAttrType for NftaSet {}
 #[neli::neli_enum(serialized_type = "u16")]
 enum NftaSetElem {
     Key = 1,
+    Flags = 3,
     Expr = 7,
+    KeyEnd = 10,
 }
 impl NlAttrType for NftaSetElem {}
 
@@ -86,10 +104,137 @@ enum NftaCounter {
 }
 impl NlAttrType for NftaCounter {}
 
+// defined by userspace nftables
+const TYPE_IPADDR: u32 = 7;
+const TYPE_IP6ADDR: u32 = 8;
+const TYPE_ETHERADDR: u32 = 9;
+const TYPE_INET_PROTOCOL: u32 = 12;
+const TYPE_INET_SERVICE: u32 = 13;
+const TYPE_MARK: u32 = 19;
+const TYPE_IFNAME: u32 = 41;
+
+// concatenated types are packed into the key type, first type in the
+// highest bits
+const TYPE_BITS: u32 = 6;
+
 pub(super) struct NftSet {
     pub family: u8,
     pub table: String,
     pub name: String,
+    key_types: Vec<u32>,
+    interval: bool,
+}
+
+pub(super) fn nft_family_name(family: u8) -> &'static str {
+    // NFPROTO_*
+    match family {
+        1 => "inet",
+        2 => "ip",
+        3 => "arp",
+        5 => "netdev",
+        7 => "bridge",
+        10 => "ip6",
+        _ => "unknown",
+    }
+}
+
+pub(super) struct NftChain {
+    pub family: u8,
+    pub table: String,
+    pub name: String,
+}
+
+fn parse_chain(resp: &Nfgenmsg<NftaChain>) -> Option<NftChain> {
+    let mut table = None;
+    let mut name = None;
+    for attr in resp.attrs.iter() {
+        match attr.nla_type().nla_type() {
+            NftaChain::Table => {
+                table = attr.get_payload_as_with_len::<String>().ok();
+            }
+            NftaChain::Name => {
+                name = attr.get_payload_as_with_len::<String>().ok();
+            }
+            _ => (),
+        }
+    }
+
+    Some(NftChain {
+        family: resp.family,
+        table: table?,
+        name: name?,
+    })
+}
+
+pub(super) struct NftChainIter {
+    recv: NlRouterReceiverHandle<NftMsg, Nfgenmsg<NftaChain>>,
+}
+
+impl Iterator for NftChainIter {
+    type Item = Result<NftChain>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nlmsg = match self.recv.next_typed::<NftMsg, Nfgenmsg<NftaChain>>() {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => {
+                    return Some(Err(err).context("failed to recv chain from nft"));
+                }
+                None => return None,
+            };
+
+            if let Some(chain) = nlmsg.get_payload().and_then(parse_chain) {
+                return Some(Ok(chain));
+            }
+        }
+    }
+}
+
+// a rule is identified by the chain it belongs to
+fn parse_rule(resp: &Nfgenmsg<NftaRule>) -> Option<NftChain> {
+    let mut table = None;
+    let mut chain = None;
+    for attr in resp.attrs.iter() {
+        match attr.nla_type().nla_type() {
+            NftaRule::Table => {
+                table = attr.get_payload_as_with_len::<String>().ok();
+            }
+            NftaRule::Chain => {
+                chain = attr.get_payload_as_with_len::<String>().ok();
+            }
+            _ => (),
+        }
+    }
+
+    Some(NftChain {
+        family: resp.family,
+        table: table?,
+        name: chain?,
+    })
+}
+
+pub(super) struct NftRuleIter {
+    recv: NlRouterReceiverHandle<NftMsg, Nfgenmsg<NftaRule>>,
+}
+
+impl Iterator for NftRuleIter {
+    type Item = Result<NftChain>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let nlmsg = match self.recv.next_typed::<NftMsg, Nfgenmsg<NftaRule>>() {
+                Some(Ok(msg)) => msg,
+                Some(Err(err)) => {
+                    return Some(Err(err).context("failed to recv rule from nft"));
+                }
+                None => return None,
+            };
+
+            if let Some(chain) = nlmsg.get_payload().and_then(parse_rule) {
+                return Some(Ok(chain));
+            }
+        }
+    }
 }
 
 fn parse_set(resp: &Nfgenmsg<NftaSet>) -> Option<NftSet> {
@@ -117,18 +262,41 @@ fn parse_set(resp: &Nfgenmsg<NftaSet>) -> Option<NftSet> {
         }
     }
 
+    // the kernel omits the attribute when no flag is set; dynamic sets
+    // (NFT_SET_EVAL) are named and should be collected
     const NFT_SET_ANONYMOUS: u32 = 1;
-    if flags.is_none_or(|flags| flags & NFT_SET_ANONYMOUS > 0) {
+    const NFT_SET_INTERVAL: u32 = 4;
+    let flags = flags.unwrap_or(0);
+    if flags & NFT_SET_ANONYMOUS > 0 {
         return None;
     }
 
-    // defined by userspace nftables
-    const TYPE_IPADDR: u32 = 7;
-    const TYPE_IP6ADDR: u32 = 8;
-    const TYPE_ETHERADDR: u32 = 9;
-    match key_type {
-        Some(TYPE_IPADDR | TYPE_IP6ADDR | TYPE_ETHERADDR) => (),
-        _ => return None,
+    let mut key_type = key_type?;
+    let mut key_types = Vec::new();
+    while key_type != 0 {
+        key_types.push(key_type & ((1 << TYPE_BITS) - 1));
+        key_type >>= TYPE_BITS;
+    }
+    key_types.reverse();
+
+    // a set must be keyed on at least one address to be useful
+    let supported = key_types.iter().all(|ty| {
+        matches!(
+            *ty,
+            TYPE_IPADDR
+                | TYPE_IP6ADDR
+                | TYPE_ETHERADDR
+                | TYPE_INET_PROTOCOL
+                | TYPE_INET_SERVICE
+                | TYPE_MARK
+                | TYPE_IFNAME
+        )
+    });
+    let has_addr = key_types
+        .iter()
+        .any(|ty| matches!(*ty, TYPE_IPADDR | TYPE_IP6ADDR | TYPE_ETHERADDR));
+    if !supported || !has_addr {
+        return None;
     }
 
     match (table, name) {
@@ -136,6 +304,8 @@ fn parse_set(resp: &Nfgenmsg<NftaSet>) -> Option<NftSet> {
             family,
             table,
             name,
+            key_types,
+            interval: flags & NFT_SET_INTERVAL > 0,
         }),
         _ => None,
     }
@@ -213,38 +383,129 @@ fn parse_set_elem_expr(expr: GenlAttrHandle<NftaExpr>) -> Option<(u64, u64)> {
     }
 }
 
-fn parse_set_elem_key(key: GenlAttrHandle<NftaData>) -> Option<String> {
-    let mut value = None;
+fn format_set_elem_key(ty: u32, value: &[u8]) -> Option<(usize, String)> {
+    let (len, s) = match ty {
+        TYPE_IPADDR => {
+            let octets = <[u8; 4]>::try_from(value.get(..4)?).ok()?;
+            (4, net::IpAddr::from(octets).to_string())
+        }
+        TYPE_IP6ADDR => {
+            let segments = <[u8; 16]>::try_from(value.get(..16)?).ok()?;
+            (16, net::IpAddr::from(segments).to_string())
+        }
+        TYPE_ETHERADDR => {
+            let mac = value.get(..6)?;
+            let s = format!(
+                "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+                mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+            );
+            (6, s)
+        }
+        TYPE_INET_PROTOCOL => (1, value.first()?.to_string()),
+        TYPE_INET_SERVICE => {
+            let port = u16::from_be_bytes(value.get(..2)?.try_into().ok()?);
+            (2, port.to_string())
+        }
+        TYPE_MARK => {
+            let mark = u32::from_ne_bytes(value.get(..4)?.try_into().ok()?);
+            (4, format!("{mark:#010x}"))
+        }
+        TYPE_IFNAME => {
+            let name = value.get(..16)?;
+            let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+            (16, String::from_utf8_lossy(&name[..end]).into_owned())
+        }
+        _ => return None,
+    };
+
+    Some((len, s))
+}
+
+// the prefix length if the inclusive range is a single cidr block
+fn cidr_prefix_len(start: &[u8], end: &[u8]) -> Option<u32> {
+    let to_u128 = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .fold(0u128, |acc, &b| acc.wrapping_shl(8) | b as u128)
+    };
+    let bits = start.len() as u32 * 8;
+    let host_mask = to_u128(start) ^ to_u128(end);
+
+    let host_bits = u128::BITS - host_mask.leading_zeros();
+    let aligned = host_mask.count_ones() == host_bits
+        && to_u128(start) & host_mask == 0
+        && to_u128(end) & host_mask == host_mask;
+    aligned.then_some(bits - host_bits)
+}
+
+fn format_set_elem_key_range(ty: u32, start: &[u8], end: &[u8]) -> Option<(usize, String)> {
+    let (len, start_str) = format_set_elem_key(ty, start)?;
+    let (start, end) = (start.get(..len)?, end.get(..len)?);
+    if start == end {
+        return Some((len, start_str));
+    }
+
+    if matches!(ty, TYPE_IPADDR | TYPE_IP6ADDR) {
+        if let Some(prefix_len) = cidr_prefix_len(start, end) {
+            return Some((len, format!("{start_str}/{prefix_len}")));
+        }
+    }
+
+    let (_, end_str) = format_set_elem_key(ty, end)?;
+    Some((len, format!("{start_str}-{end_str}")))
+}
+
+// end is inclusive
+fn format_set_elem_keys(key_types: &[u32], start: &[u8], end: Option<&[u8]>) -> Option<String> {
+    let (mut start, mut end) = (start, end.unwrap_or(start));
+
+    // each part of a concatenated key is padded to 32 bits
+    let mut parts = Vec::with_capacity(key_types.len());
+    for &ty in key_types {
+        let (len, part) = format_set_elem_key_range(ty, start, end)?;
+        start = start.get(len.next_multiple_of(4)..).unwrap_or_default();
+        end = end.get(len.next_multiple_of(4)..).unwrap_or_default();
+        parts.push(part);
+    }
+
+    // same as how nft lists concatenated keys
+    Some(parts.join(" . "))
+}
+
+fn parse_set_elem_key(key: GenlAttrHandle<NftaData>) -> Option<Vec<u8>> {
     for attr in key.iter() {
         if attr.nla_type().nla_type() == &NftaData::Value {
-            value = Some(attr.payload().as_ref());
-            break;
+            return Some(attr.payload().as_ref().to_vec());
         }
     }
 
-    value.and_then(|value| {
-        if let Ok(octets) = <&[u8; 4]>::try_from(value) {
-            Some(net::IpAddr::from(*octets).to_string())
-        } else if let Ok(mac) = <&[u8; 6]>::try_from(value) {
-            Some(format!(
-                "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
-                mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
-            ))
-        } else if let Ok(segments) = <&[u8; 16]>::try_from(value) {
-            Some(net::IpAddr::from(*segments).to_string())
-        } else {
-            None
-        }
-    })
+    None
+}
+
+struct NftSetElem {
+    key: Vec<u8>,
+    // set by sets that store both ends in one element
+    key_end: Option<Vec<u8>>,
+    // set by the end elements of sets that store ranges in two elements
+    interval_end: bool,
+    bytes: Option<u64>,
 }
 
-fn parse_set_elem(elem: GenlAttrHandle<NftaSetElem>) -> Option<NftSetCounter> {
-    let mut addr = None;
+fn parse_set_elem(elem: GenlAttrHandle<NftaSetElem>) -> Option<NftSetElem> {
+    let mut key = None;
+    let mut key_end = None;
+    let mut flags = None;
     let mut counter = None;
     for attr in elem.iter() {
         match attr.nla_type().nla_type() {
             NftaSetElem::Key => {
-                addr = attr.get_attr_handle().ok().and_then(parse_set_elem_key);
+                key = attr.get_attr_handle().ok().and_then(parse_set_elem_key);
+            }
+            NftaSetElem::KeyEnd => {
+                key_end = attr.get_attr_handle().ok().and_then(parse_set_elem_key);
+            }
+            NftaSetElem::Flags => {
+                flags = attr.get_payload_as::<u32>().map(u32::swap_bytes).ok();
             }
             NftaSetElem::Expr => {
                 counter = attr.get_attr_handle().ok().and_then(parse_set_elem_expr);
@@ -253,22 +514,25 @@ fn parse_set_elem(elem: GenlAttrHandle<NftaSetElem>) -> Option<NftSetCounter> {
         }
     }
 
-    match (addr, counter) {
-        (Some(addr), Some((bytes, _))) => Some(NftSetCounter { addr, bytes }),
-        _ => None,
-    }
+    const NFT_SET_ELEM_INTERVAL_END: u32 = 1;
+    Some(NftSetElem {
+        key: key?,
+        key_end,
+        interval_end: flags.unwrap_or(0) & NFT_SET_ELEM_INTERVAL_END > 0,
+        bytes: counter.map(|(bytes, _)| bytes),
+    })
 }
 
 fn parse_set_elem_list(
     list: GenlAttrHandle<NftaList>,
     base_idx: usize,
-) -> Option<(usize, NftSetCounter)> {
+) -> Option<(usize, NftSetElem)> {
     let elems = list.get_attrs();
 
     let mut idx = base_idx;
     while idx < elems.len() {
-        if let Some(counter) = elems[idx].get_attr_handle().ok().and_then(parse_set_elem) {
-            return Some((idx, counter));
+        if let Some(elem) = elems[idx].get_attr_handle().ok().and_then(parse_set_elem) {
+            return Some((idx, elem));
         }
         idx += 1;
     }
@@ -276,22 +540,60 @@ fn parse_set_elem_list(
     None
 }
 
+// pair the start elements of an interval set with their end elements, whose
+// keys are one past the ends of the ranges
+fn pair_set_elem_intervals(key_types: &[u32], elems: Vec<NftSetElem>) -> Vec<NftSetCounter> {
+    let (mut ends, starts): (Vec<_>, Vec<_>) =
+        elems.into_iter().partition(|elem| elem.interval_end);
+    ends.sort_by(|a, b| a.key.cmp(&b.key));
+
+    starts
+        .into_iter()
+        .filter_map(|elem| {
+            let bytes = elem.bytes?;
+
+            let end = ends
+                .iter()
+                .find(|end| end.key.len() == elem.key.len() && end.key > elem.key);
+            let end = match end {
+                Some(end) => {
+                    let mut end = end.key.clone();
+                    for b in end.iter_mut().rev() {
+                        *b = b.wrapping_sub(1);
+                        if *b != 0xff {
+                            break;
+                        }
+                    }
+                    end
+                }
+                // a range without an end element extends to the max key
+                None => vec![0xff; elem.key.len()],
+            };
+
+            let addr = format_set_elem_keys(key_types, &elem.key, Some(&end))?;
+            Some(NftSetCounter { addr, bytes })
+        })
+        .collect()
+}
+
 pub(super) struct NftSetCounter {
     pub addr: String,
     pub bytes: u64,
 }
 
 pub(super) struct NftSetCounterIter {
-    recv: NlRouterReceiverHandle<NftMsg, Nfgenmsg<NftaSetElemList>>,
+    // None when the elements are already in cur_nlmsg
+    recv: Option<NlRouterReceiverHandle<NftMsg, Nfgenmsg<NftaSetElemList>>>,
+    key_types: Vec<u32>,
+    interval: bool,
+    intervals: Option<std::vec::IntoIter<NftSetCounter>>,
     cur_nlmsg: Option<Nlmsghdr<NftMsg, Nfgenmsg<NftaSetElemList>>>,
     cur_attr: usize,
     cur_elem: usize,
 }
 
-impl Iterator for NftSetCounterIter {
-    type Item = Result<NftSetCounter>;
-
-    fn next(&mut self) -> Option<Self::Item> {
+impl NftSetCounterIter {
+    fn next_elem(&mut self) -> Option<Result<NftSetElem>> {
         loop {
             if let Some(resp) = self
                 .cur_nlmsg
@@ -302,13 +604,13 @@ impl Iterator for NftSetCounterIter {
                 while self.cur_attr < attrs.len() {
                     let attr = &attrs[self.cur_attr];
                     if attr.nla_type().nla_type() == &NftaSetElemList::Elements {
-                        if let Some((idx, counter)) = attr
+                        if let Some((idx, elem)) = attr
                             .get_attr_handle::<NftaList>()
                             .ok()
                             .and_then(|list| parse_set_elem_list(list, self.cur_elem))
                         {
                             self.cur_elem = idx + 1;
-                            return Some(Ok(counter));
+                            return Some(Ok(elem));
                         }
                     }
 
@@ -317,7 +619,8 @@ impl Iterator for NftSetCounterIter {
                 }
             }
 
-            let nlmsg = match self.recv.next_typed::<NftMsg, Nfgenmsg<NftaSetElemList>>() {
+            let recv = self.recv.as_mut()?;
+            let nlmsg = match recv.next_typed::<NftMsg, Nfgenmsg<NftaSetElemList>>() {
                 Some(Ok(msg)) => msg,
                 Some(Err(err)) => {
                     return Some(Err(err).context("failed to recv set elem from nft"));
@@ -332,7 +635,53 @@ impl Iterator for NftSetCounterIter {
     }
 }
 
+impl Iterator for NftSetCounterIter {
+    type Item = Result<NftSetCounter>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // sets that store a range in one element can be handled one by one
+        if !self.interval || self.key_types.len() > 1 {
+            loop {
+                let elem = match self.next_elem()? {
+                    Ok(elem) => elem,
+                    Err(err) => return Some(Err(err)),
+                };
+                let Some(bytes) = elem.bytes else {
+                    continue;
+                };
+
+                let addr =
+                    format_set_elem_keys(&self.key_types, &elem.key, elem.key_end.as_deref());
+                if let Some(addr) = addr {
+                    return Some(Ok(NftSetCounter { addr, bytes }));
+                }
+            }
+        }
+
+        if self.intervals.is_none() {
+            let mut elems = Vec::new();
+            while let Some(elem) = self.next_elem() {
+                match elem {
+                    Ok(elem) => elems.push(elem),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+
+            let counters = pair_set_elem_intervals(&self.key_types, elems);
+            self.intervals = Some(counters.into_iter());
+        }
+
+        self.intervals.as_mut()?.next().map(Ok)
+    }
+}
+
 impl super::Linux {
+    fn nf_sock(&self) -> Result<&NlRouter> {
+        self.nf_sock
+            .as_ref()
+            .context("nfnetlink socket is unavailable")
+    }
+
     pub(super) fn parse_nfnetlink(&self) -> Result<NftSetIter> {
         let req = Nfgenmsg::<NftaSet> {
             family: 0,
@@ -341,13 +690,43 @@ impl super::Linux {
             attrs: Default::default(),
         };
         let recv = self
-            .nf_sock
+            .nf_sock()?
             .send(NftMsg::Getset, NlmF::DUMP, NlPayload::Payload(req))
             .context("failed to send to nft")?;
 
         Ok(NftSetIter { recv })
     }
 
+    pub(super) fn parse_nft_chains(&self) -> Result<NftChainIter> {
+        let req = Nfgenmsg::<NftaChain> {
+            family: 0,
+            version: NFNETLINK_V0,
+            res_id: 0,
+            attrs: Default::default(),
+        };
+        let recv = self
+            .nf_sock()?
+            .send(NftMsg::Getchain, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to nft")?;
+
+        Ok(NftChainIter { recv })
+    }
+
+    pub(super) fn parse_nft_rules(&self) -> Result<NftRuleIter> {
+        let req = Nfgenmsg::<NftaRule> {
+            family: 0,
+            version: NFNETLINK_V0,
+            res_id: 0,
+            attrs: Default::default(),
+        };
+        let recv = self
+            .nf_sock()?
+            .send(NftMsg::Getrule, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to nft")?;
+
+        Ok(NftRuleIter { recv })
+    }
+
     pub(super) fn parse_nft_set(&self, set: &NftSet) -> Result<NftSetCounterIter> {
         let attrs = [
             NlattrBuilder::default()
@@ -374,15 +753,255 @@ impl super::Linux {
             attrs: GenlBuffer::from_iter(attrs),
         };
         let recv = self
-            .nf_sock
+            .nf_sock()?
             .send(NftMsg::Getsetelem, NlmF::DUMP, NlPayload::Payload(req))
             .context("failed to send to nft")?;
 
         Ok(NftSetCounterIter {
-            recv,
+            recv: Some(recv),
+            key_types: set.key_types.clone(),
+            interval: set.interval,
+            intervals: None,
             cur_nlmsg: None,
             cur_attr: 0,
             cur_elem: 0,
         })
     }
 }
+
+#[cfg(test)]
+fn nft_attr<T: NlAttrType>(ty: T, payload: Vec<u8>) -> neli::genl::Nlattr<T, Buffer> {
+    NlattrBuilder::default()
+        .nla_type(AttrTypeBuilder::default().nla_type(ty).build().unwrap())
+        .nla_payload(payload)
+        .build()
+        .unwrap()
+}
+
+// parses the message as if it were sent by the kernel
+#[cfg(test)]
+fn nft_msg<T: NlAttrType>(family: u8, attrs: GenlBuffer<T, Buffer>) -> Nfgenmsg<T> {
+    let msg = Nfgenmsg {
+        family,
+        version: NFNETLINK_V0,
+        res_id: 0,
+        attrs,
+    };
+
+    let mut buf = io::Cursor::new(Vec::new());
+    msg.to_bytes(&mut buf).unwrap();
+    let len = buf.get_ref().len();
+    buf.set_position(0);
+    Nfgenmsg::from_bytes_with_input(&mut buf, len).unwrap()
+}
+
+// returns inet filter allow, an ipv4_addr . inet_service set, and the counters
+// of its elements, which are keys and byte counts
+#[cfg(test)]
+pub(super) fn set_fixture(elems: &[([u8; 8], u64)]) -> (NftSet, NftSetCounterIter) {
+    let key_type = (TYPE_IPADDR << TYPE_BITS) | TYPE_INET_SERVICE;
+    let mut attrs = GenlBuffer::new();
+    attrs.push(nft_attr(NftaSet::Table, b"filter\0".to_vec()));
+    attrs.push(nft_attr(NftaSet::Name, b"allow\0".to_vec()));
+    attrs.push(nft_attr(NftaSet::KeyType, key_type.to_be_bytes().to_vec()));
+    let set = parse_set(&nft_msg(1, attrs)).unwrap();
+
+    let mut list = nft_attr(NftaSetElemList::Elements, Vec::new());
+    for (key, bytes) in elems {
+        let key = nft_attr(NftaSetElem::Key, Vec::new())
+            .nest(&nft_attr(NftaData::Value, key.to_vec()))
+            .unwrap();
+        let counter = nft_attr(NftaExpr::Data, Vec::new())
+            .nest(&nft_attr(NftaCounter::Bytes, bytes.to_be_bytes().to_vec()))
+            .unwrap()
+            .nest(&nft_attr(NftaCounter::Packets, 1u64.to_be_bytes().to_vec()))
+            .unwrap();
+        let expr = nft_attr(NftaSetElem::Expr, Vec::new())
+            .nest(&nft_attr(NftaExpr::Name, b"counter\0".to_vec()))
+            .unwrap()
+            .nest(&counter)
+            .unwrap();
+        let elem = nft_attr(NftaList::Elem, Vec::new())
+            .nest(&key)
+            .unwrap()
+            .nest(&expr)
+            .unwrap();
+        list = list.nest(&elem).unwrap();
+    }
+    let mut attrs = GenlBuffer::new();
+    attrs.push(list);
+    let nlmsg = neli::nl::NlmsghdrBuilder::default()
+        .nl_type(NftMsg::Getsetelem)
+        .nl_flags(NlmF::MULTI)
+        .nl_payload(NlPayload::Payload(nft_msg(1, attrs)))
+        .build()
+        .unwrap();
+
+    let counters = NftSetCounterIter {
+        recv: None,
+        key_types: set.key_types.clone(),
+        interval: set.interval,
+        intervals: None,
+        cur_nlmsg: Some(nlmsg),
+        cur_attr: 0,
+        cur_elem: 0,
+    };
+
+    (set, counters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_msg(flags: Option<u32>, key_type: u32) -> Nfgenmsg<NftaSet> {
+        let mut attrs = GenlBuffer::new();
+        attrs.push(nft_attr(NftaSet::Table, b"filter\0".to_vec()));
+        attrs.push(nft_attr(NftaSet::Name, b"blocklist\0".to_vec()));
+        // nftables attributes are big-endian
+        if let Some(flags) = flags {
+            attrs.push(nft_attr(NftaSet::Flags, flags.to_be_bytes().to_vec()));
+        }
+        attrs.push(nft_attr(NftaSet::KeyType, key_type.to_be_bytes().to_vec()));
+
+        nft_msg(1, attrs)
+    }
+
+    #[test]
+    fn set_flags() {
+        const NFT_SET_ANONYMOUS: u32 = 0x1;
+        const NFT_SET_INTERVAL: u32 = 0x4;
+        const NFT_SET_TIMEOUT: u32 = 0x10;
+        const NFT_SET_EVAL: u32 = 0x20;
+
+        // flags, key type, and whether the set is an interval set
+        let cases = [
+            (Some(NFT_SET_EVAL), TYPE_IPADDR, Some(false)),
+            (
+                Some(NFT_SET_EVAL | NFT_SET_TIMEOUT),
+                TYPE_IP6ADDR,
+                Some(false),
+            ),
+            // the kernel omits the attribute when no flag is set
+            (None, TYPE_IPADDR, Some(false)),
+            (Some(NFT_SET_INTERVAL), TYPE_IPADDR, Some(true)),
+            (Some(NFT_SET_ANONYMOUS), TYPE_IPADDR, None),
+            (Some(NFT_SET_ANONYMOUS | NFT_SET_EVAL), TYPE_IPADDR, None),
+            // not keyed on an address
+            (None, TYPE_INET_SERVICE, None),
+        ];
+        for (flags, key_type, expected) in cases {
+            let set = parse_set(&set_msg(flags, key_type));
+            assert_eq!(set.as_ref().map(|set| set.interval), expected, "{flags:?}");
+
+            if let Some(set) = set {
+                assert_eq!(nft_family_name(set.family), "inet");
+                assert_eq!(set.table, "filter");
+                assert_eq!(set.name, "blocklist");
+                assert_eq!(set.key_types, [key_type]);
+            }
+        }
+    }
+
+    #[test]
+    fn set_elem_keys() {
+        let check = |key_types: &[u32], start: &[u8], end: Option<&[u8]>, expected| {
+            assert_eq!(
+                format_set_elem_keys(key_types, start, end).as_deref(),
+                expected
+            );
+        };
+
+        check(&[TYPE_IPADDR], &[192, 168, 1, 1], None, Some("192.168.1.1"));
+
+        // each part of a concatenated key is padded to 32 bits
+        let ip_port = [TYPE_IPADDR, TYPE_INET_SERVICE];
+        let key = [10, 0, 0, 1, 0x01, 0xbb, 0, 0];
+        check(&ip_port, &key, None, Some("10.0.0.1 . 443"));
+        check(&ip_port, &key, Some(&key), Some("10.0.0.1 . 443"));
+        check(
+            &[TYPE_IPADDR, TYPE_INET_PROTOCOL, TYPE_INET_SERVICE],
+            &[10, 0, 0, 1, 6, 0, 0, 0, 0x00, 0x16, 0, 0],
+            None,
+            Some("10.0.0.1 . 6 . 22"),
+        );
+
+        // ranges of concatenated keys store both ends in one element
+        let key_end = [10, 0, 0, 255, 0x01, 0xbb, 0, 0];
+        check(
+            &ip_port,
+            &key,
+            Some(&key_end),
+            Some("10.0.0.1-10.0.0.255 . 443"),
+        );
+        let key_end = [10, 0, 0, 1, 0x1f, 0x90, 0, 0];
+        check(&ip_port, &key, Some(&key_end), Some("10.0.0.1 . 443-8080"));
+
+        // truncated
+        check(&ip_port, &key[..5], None, None);
+    }
+
+    #[test]
+    fn cidr_prefix() {
+        // start, end, and the prefix length if the range is a cidr block
+        let cases: [([u8; 4], [u8; 4], Option<u32>); 7] = [
+            ([1, 2, 3, 0], [1, 2, 3, 255], Some(24)),
+            ([10, 0, 0, 0], [10, 255, 255, 255], Some(8)),
+            ([192, 168, 1, 7], [192, 168, 1, 7], Some(32)),
+            ([0, 0, 0, 0], [255, 255, 255, 255], Some(0)),
+            ([10, 0, 0, 1], [10, 0, 0, 5], None),
+            // the size is a power of two but the start is not aligned
+            ([10, 0, 0, 4], [10, 0, 0, 11], None),
+            ([10, 0, 0, 0], [10, 0, 0, 254], None),
+        ];
+        for (start, end, expected) in cases {
+            assert_eq!(cidr_prefix_len(&start, &end), expected, "{start:?}");
+        }
+
+        let start = net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0).octets();
+        let end = net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0xffff, 0xffff, 0xffff, 0xffff);
+        assert_eq!(cidr_prefix_len(&start, &end.octets()), Some(64));
+    }
+
+    #[test]
+    fn set_elem_intervals() {
+        let elem = |key: [u8; 4], interval_end: bool, bytes: Option<u64>| NftSetElem {
+            key: key.to_vec(),
+            key_end: None,
+            interval_end,
+            bytes,
+        };
+
+        // the end elements are one past the ends of the ranges and can come
+        // in any order
+        let elems = vec![
+            elem([10, 0, 0, 6], true, None),
+            elem([1, 2, 4, 0], true, None),
+            elem([1, 2, 3, 0], false, Some(100)),
+            elem([10, 0, 0, 1], false, Some(200)),
+            // without a counter
+            elem([172, 16, 0, 0], false, None),
+            elem([172, 17, 0, 0], true, None),
+            // without an end element
+            elem([192, 168, 0, 0], false, Some(300)),
+        ];
+        let counters = pair_set_elem_intervals(&[TYPE_IPADDR], elems);
+        let counters: Vec<_> = counters
+            .iter()
+            .map(|counter| (counter.addr.as_str(), counter.bytes))
+            .collect();
+        assert_eq!(
+            counters,
+            [
+                ("1.2.3.0/24", 100),
+                ("10.0.0.1-10.0.0.5", 200),
+                ("192.168.0.0-255.255.255.255", 300),
+            ]
+        );
+
+        // an open-ended range that is also a cidr block
+        let elems = vec![elem([128, 0, 0, 0], false, Some(1))];
+        let counters = pair_set_elem_intervals(&[TYPE_IPADDR], elems);
+        assert_eq!(counters[0].addr, "128.0.0.0/1");
+    }
+}
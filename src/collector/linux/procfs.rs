@@ -3,8 +3,9 @@
 
 use anyhow::{Context, Result, anyhow};
 use std::{
-    fs,
+    collections, fs,
     io::{self, BufRead},
+    iter, net,
 };
 
 #[derive(Default)]
@@ -18,6 +19,8 @@ pub(super) struct MemInfo {
 pub(super) struct Stat {
     pub cpu: String,
     pub idle_ticks: u64,
+    pub busy_ticks: u64,
+    pub total_ticks: u64,
 }
 
 #[derive(Default)]
@@ -26,16 +29,95 @@ pub(super) struct VmStat {
     pub pswpout: u64,
 }
 
+#[derive(Default)]
+pub(super) struct NetSnmp {
+    pub tcp_out_segs: u64,
+    pub tcp_retrans_segs: u64,
+}
+
+#[derive(Default)]
+pub(super) struct NetSnmp6 {
+    pub ip6_in_receives: u64,
+    pub ip6_in_discards: u64,
+    pub ip6_reasm_fails: u64,
+    pub icmp6_in_errors: u64,
+    pub icmp6_in_router_advertisements: u64,
+}
+
+// indexed by TCP_ESTABLISHED and friends
+pub(super) const TCP_STATES: [&str; 12] = [
+    "unknown",
+    "established",
+    "syn_sent",
+    "syn_recv",
+    "fin_wait1",
+    "fin_wait2",
+    "time_wait",
+    "close",
+    "close_wait",
+    "last_ack",
+    "listen",
+    "closing",
+];
+
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+pub(super) struct NetListenPort {
+    pub proto: &'static str,
+    pub addr: net::IpAddr,
+    pub port: u16,
+}
+
+// the address is printed as native-endian 32-bit words
+fn parse_net_addr(col: &str) -> Option<(net::IpAddr, u16)> {
+    let (addr, port) = col.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let mut bytes = Vec::with_capacity(16);
+    for idx in (0..addr.len()).step_by(8) {
+        let word = u32::from_str_radix(addr.get(idx..idx + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+
+    let addr = if let Ok(octets) = <[u8; 4]>::try_from(bytes.as_slice()) {
+        net::IpAddr::from(octets)
+    } else {
+        net::IpAddr::from(<[u8; 16]>::try_from(bytes.as_slice()).ok()?)
+    };
+
+    Some((addr, port))
+}
+
+pub(super) struct NetEphemeralPorts {
+    pub total: u64,
+    // distinct local ports in the range, which approximates the usage
+    pub tcp_used: u64,
+    pub udp_used: u64,
+}
+
+pub(super) struct NetSoftnet {
+    pub cpu: String,
+    pub processed: u64,
+    pub dropped: u64,
+    pub time_squeeze: u64,
+}
+
+pub(super) struct HostInfo {
+    pub hostname: String,
+    pub osrelease: String,
+}
+
 pub(super) struct PidMountInfo {
     pub major_minor: String,
     pub mount_source: String,
     pub mount_point: String,
+    pub readonly: bool,
     pub total: u64,
     pub avail: u64,
 }
 
 fn parse_stat_line(line: &str) -> Result<Stat> {
     // 0:cpu 1:user 2:nice 3:system 4:idle 5:iowait
+    // 6:irq 7:softirq 8:steal 9:guest 10:guest_nice
     let cols: Vec<&str> = line.split_ascii_whitespace().collect();
     if cols.len() < 5 {
         return Err(anyhow!("failed to parse stat"));
@@ -45,7 +127,20 @@ fn parse_stat_line(line: &str) -> Result<Stat> {
     let [_user_ticks, _system_ticks, idle_ticks] =
         [cols[1], cols[3], cols[4]].map(|col| col.parse().unwrap_or(0));
 
-    Ok(Stat { cpu, idle_ticks })
+    // guest time is already accounted in user and nice
+    let total_ticks = cols[1..cols.len().min(9)]
+        .iter()
+        .map(|col| col.parse::<u64>().unwrap_or(0))
+        .sum();
+    let iowait_ticks: u64 = cols.get(5).map_or(0, |col| col.parse().unwrap_or(0));
+    let busy_ticks = total_ticks - idle_ticks - iowait_ticks;
+
+    Ok(Stat {
+        cpu,
+        idle_ticks,
+        busy_ticks,
+        total_ticks,
+    })
 }
 
 pub(super) struct StatIter {
@@ -78,14 +173,15 @@ impl Iterator for StatIter {
     }
 }
 
-fn parse_pid_mountinfo_line(line: &str) -> Result<(&str, &str, &str)> {
+fn parse_pid_mountinfo_line(line: &str) -> Result<(&str, &str, &str, &str, bool)> {
     // 0:id 1:parent_id 2:major:minor 3:root 4:mountpoint 5:options
     // optional fields... n:seperator
     // n+1:fs_type n+2:src n+3:super
     let cols: Vec<&str> = line.split_ascii_whitespace().collect();
     let sep_min = 6;
-    let sep = cols[sep_min..]
+    let sep = cols
         .iter()
+        .skip(sep_min)
         .position(|&col| col == "-")
         .map_or(0, |idx| sep_min + idx);
     if sep < sep_min || cols.len() < sep + 3 {
@@ -94,13 +190,19 @@ fn parse_pid_mountinfo_line(line: &str) -> Result<(&str, &str, &str)> {
 
     let major_minor = cols[2];
     let dst = cols[4];
+    let fs_type = cols[sep + 1];
     let src = cols[sep + 2];
 
-    Ok((major_minor, src, dst))
+    // the super options are ro when the fs is remounted ro on errors
+    let is_ro = |opts: &str| opts.split(',').any(|opt| opt == "ro");
+    let readonly = is_ro(cols[5]) || cols.get(sep + 3).is_some_and(|opts| is_ro(opts));
+
+    Ok((major_minor, fs_type, src, dst, readonly))
 }
 
 pub(super) struct PidMountInfoIter {
     reader: io::BufReader<fs::File>,
+    include_virtual: bool,
 }
 
 impl Iterator for PidMountInfoIter {
@@ -116,19 +218,21 @@ impl Iterator for PidMountInfoIter {
             };
 
             let res = parse_pid_mountinfo_line(&line);
-            if let Ok((_, src, _)) = res {
-                if !src.starts_with("/") {
+            if let Ok((_, fs_type, src, _, _)) = res {
+                let is_virtual = self.include_virtual && fs_type == "tmpfs";
+                if !src.starts_with("/") && !is_virtual {
                     continue;
                 }
             }
 
-            let info = res.and_then(|(major_minor, src, dst)| {
+            let info = res.and_then(|(major_minor, _, src, dst, readonly)| {
                 let [total, _free, avail] = crate::libc::statvfs_size(dst)?;
 
                 Ok(PidMountInfo {
                     major_minor: major_minor.to_string(),
                     mount_source: src.to_string(),
                     mount_point: dst.to_string(),
+                    readonly,
                     total,
                     avail,
                 })
@@ -147,6 +251,7 @@ impl super::Linux {
         let mut mem_avail_kb = 0;
         let mut swap_total_kb = 0;
         let mut swap_free_kb = 0;
+        let mut remaining = 4;
         for line in reader.lines() {
             let line = line.context("failed to read meminfo")?;
 
@@ -158,16 +263,19 @@ impl super::Linux {
             let ty = cols[0];
             let val: u64 = cols[1].parse().unwrap_or(0);
 
+            // fields can be reordered or missing (e.g., swap is disabled)
             match ty {
                 "MemTotal:" => mem_total_kb = val,
                 "MemAvailable:" => mem_avail_kb = val,
                 "SwapTotal:" => swap_total_kb = val,
-                "SwapFree:" => {
-                    swap_free_kb = val;
-                    // we've got them all
-                    break;
-                }
-                _ => (),
+                "SwapFree:" => swap_free_kb = val,
+                _ => continue,
+            }
+
+            remaining -= 1;
+            if remaining == 0 {
+                // we've got them all
+                break;
             }
         }
 
@@ -203,8 +311,350 @@ impl super::Linux {
         Ok(VmStat { pswpin, pswpout })
     }
 
+    pub(super) fn parse_net_snmp(&self) -> Result<NetSnmp> {
+        let reader = self.procfs_open("net/snmp")?;
+        let lines: Vec<String> = reader
+            .lines()
+            .collect::<Result<_, _>>()
+            .context("failed to read snmp")?;
+
+        // each protocol has a line of names followed by a line of values
+        let mut snmp = NetSnmp::default();
+        for pair in lines.chunks(2) {
+            let [names, vals] = pair else {
+                return Err(anyhow!("failed to parse snmp"));
+            };
+            let Some(names) = names.strip_prefix("Tcp:") else {
+                continue;
+            };
+            let vals = vals
+                .strip_prefix("Tcp:")
+                .ok_or_else(|| anyhow!("failed to parse snmp"))?;
+
+            for (name, val) in iter::zip(
+                names.split_ascii_whitespace(),
+                vals.split_ascii_whitespace(),
+            ) {
+                match name {
+                    "OutSegs" => snmp.tcp_out_segs = val.parse().unwrap_or(0),
+                    "RetransSegs" => snmp.tcp_retrans_segs = val.parse().unwrap_or(0),
+                    _ => (),
+                }
+            }
+        }
+
+        Ok(snmp)
+    }
+
+    pub(super) fn parse_net_snmp6(&self) -> Result<NetSnmp6> {
+        let reader = self.procfs_open("net/snmp6")?;
+
+        let mut snmp6 = NetSnmp6::default();
+        for line in reader.lines() {
+            let line = line.context("failed to read snmp6")?;
+
+            // name value
+            let cols: Vec<&str> = line.split_ascii_whitespace().collect();
+            if cols.len() < 2 {
+                return Err(anyhow!("failed to parse snmp6"));
+            }
+            let val: u64 = cols[1].parse().unwrap_or(0);
+
+            match cols[0] {
+                "Ip6InReceives" => snmp6.ip6_in_receives = val,
+                "Ip6InDiscards" => snmp6.ip6_in_discards = val,
+                "Ip6ReasmFails" => snmp6.ip6_reasm_fails = val,
+                "Icmp6InErrors" => snmp6.icmp6_in_errors = val,
+                "Icmp6InRouterAdvertisements" => snmp6.icmp6_in_router_advertisements = val,
+                _ => (),
+            }
+        }
+
+        Ok(snmp6)
+    }
+
+    pub(super) fn parse_net_tcp(&self) -> Result<[u64; TCP_STATES.len()]> {
+        let mut counts = [0; TCP_STATES.len()];
+        for file in ["net/tcp", "net/tcp6"] {
+            // tcp6 is missing when ipv6 is disabled
+            if file == "net/tcp6" && !self.procfs_path.join(file).exists() {
+                continue;
+            }
+
+            let reader = self.procfs_open(file)?;
+            for line in reader.lines().skip(1) {
+                let line = line.with_context(|| format!("failed to read {file}"))?;
+
+                // 0:sl 1:local_address 2:rem_address 3:st ...
+                let state = line
+                    .split_ascii_whitespace()
+                    .nth(3)
+                    .and_then(|col| usize::from_str_radix(col, 16).ok())
+                    .ok_or_else(|| anyhow!("failed to parse {file}"))?;
+                if let Some(count) = counts.get_mut(state) {
+                    *count += 1;
+                }
+            }
+        }
+
+        Ok(counts)
+    }
+
+    pub(super) fn parse_net_listen(&self) -> Result<collections::BTreeSet<NetListenPort>> {
+        // TCP_LISTEN for tcp; unconnected udp sockets are in TCP_CLOSE
+        const TCP_LISTEN: u64 = 10;
+        const TCP_CLOSE: u64 = 7;
+        let files = [
+            ("net/tcp", "tcp", TCP_LISTEN),
+            ("net/tcp6", "tcp", TCP_LISTEN),
+            ("net/udp", "udp", TCP_CLOSE),
+            ("net/udp6", "udp", TCP_CLOSE),
+        ];
+
+        let mut ports = collections::BTreeSet::new();
+        for (file, proto, listen_state) in files {
+            // the v6 files are missing when ipv6 is disabled
+            if file.ends_with('6') && !self.procfs_path.join(file).exists() {
+                continue;
+            }
+
+            let reader = self.procfs_open(file)?;
+            for line in reader.lines().skip(1) {
+                let line = line.with_context(|| format!("failed to read {file}"))?;
+
+                // 0:sl 1:local_address 2:rem_address 3:st ...
+                let cols: Vec<&str> = line.split_ascii_whitespace().collect();
+                if cols.len() < 4 {
+                    return Err(anyhow!("failed to parse {file}"));
+                }
+                let state = u64::from_str_radix(cols[3], 16)
+                    .with_context(|| format!("failed to parse {file}"))?;
+                if state != listen_state {
+                    continue;
+                }
+
+                let (addr, port) =
+                    parse_net_addr(cols[1]).ok_or_else(|| anyhow!("failed to parse {file}"))?;
+                // skip connected udp sockets
+                if proto == "udp" && !cols[2].ends_with(":0000") {
+                    continue;
+                }
+
+                ports.insert(NetListenPort { proto, addr, port });
+            }
+        }
+
+        Ok(ports)
+    }
+
+    pub(super) fn parse_net_ephemeral_ports(&self) -> Result<NetEphemeralPorts> {
+        // "32768\t60999"
+        let file = "sys/net/ipv4/ip_local_port_range";
+        let range = super::read_string(self.procfs_path.join(file))?;
+        let range: Vec<u16> = range
+            .split_ascii_whitespace()
+            .map(|col| col.parse())
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("failed to parse {file}"))?;
+        let [low, high] = range[..] else {
+            return Err(anyhow!("failed to parse {file}"));
+        };
+        let range = low..=high;
+
+        let mut tcp_ports = collections::HashSet::new();
+        let mut udp_ports = collections::HashSet::new();
+        for file in ["net/tcp", "net/tcp6", "net/udp", "net/udp6"] {
+            // the v6 files are missing when ipv6 is disabled
+            if file.ends_with('6') && !self.procfs_path.join(file).exists() {
+                continue;
+            }
+
+            let ports = if file.starts_with("net/tcp") {
+                &mut tcp_ports
+            } else {
+                &mut udp_ports
+            };
+
+            let reader = self.procfs_open(file)?;
+            for line in reader.lines().skip(1) {
+                let line = line.with_context(|| format!("failed to read {file}"))?;
+
+                // 0:sl 1:local_address ...
+                let (_, port) = line
+                    .split_ascii_whitespace()
+                    .nth(1)
+                    .and_then(parse_net_addr)
+                    .ok_or_else(|| anyhow!("failed to parse {file}"))?;
+                if range.contains(&port) {
+                    ports.insert(port);
+                }
+            }
+        }
+
+        Ok(NetEphemeralPorts {
+            total: range.len() as u64,
+            tcp_used: tcp_ports.len() as u64,
+            udp_used: udp_ports.len() as u64,
+        })
+    }
+
+    pub(super) fn parse_net_softnet(&self) -> Result<Vec<NetSoftnet>> {
+        let reader = self.procfs_open("net/softnet_stat")?;
+
+        let mut softnets = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line.context("failed to read softnet_stat")?;
+
+            // 0:processed 1:dropped 2:time_squeeze ... 12:cpu (since 5.10)
+            let cols: Vec<u64> = line
+                .split_ascii_whitespace()
+                .map(|col| u64::from_str_radix(col, 16).unwrap_or(0))
+                .collect();
+            if cols.len() < 3 {
+                return Err(anyhow!("failed to parse softnet_stat"));
+            }
+
+            // rows are only printed for online cpus
+            let cpu = cols.get(12).map_or(idx as u64, |&cpu| cpu);
+
+            softnets.push(NetSoftnet {
+                cpu: format!("cpu{cpu}"),
+                processed: cols[0],
+                dropped: cols[1],
+                time_squeeze: cols[2],
+            });
+        }
+
+        Ok(softnets)
+    }
+
+    pub(super) fn parse_sys_kernel(&self) -> Result<HostInfo> {
+        let hostname = super::read_string(self.procfs_path.join("sys/kernel/hostname"))?;
+        let osrelease = super::read_string(self.procfs_path.join("sys/kernel/osrelease"))?;
+
+        Ok(HostInfo {
+            hostname,
+            osrelease,
+        })
+    }
+
     pub(super) fn parse_self_mountinfo(&self) -> Result<PidMountInfoIter> {
         let reader = self.procfs_open("self/mountinfo")?;
-        Ok(PidMountInfoIter { reader })
+        Ok(PidMountInfoIter {
+            reader,
+            include_virtual: self.fs_include_virtual,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stat_line() {
+        // line, cpu, idle, busy, and total ticks
+        let cases = [
+            (
+                "cpu0 2358 178 1221 68948 602 0 201 0 0 0",
+                "cpu0",
+                68948,
+                3958,
+                73508,
+            ),
+            // guest ticks are excluded from the total
+            ("cpu1 100 0 50 800 10 0 5 0 40 0", "cpu1", 800, 155, 965),
+            // iowait and later columns are missing on old kernels
+            ("cpu2 10 0 5 100", "cpu2", 100, 15, 115),
+        ];
+        for (line, cpu, idle, busy, total) in cases {
+            let stat = parse_stat_line(line).unwrap();
+            assert_eq!(stat.cpu, cpu);
+            assert_eq!(stat.idle_ticks, idle, "{line}");
+            assert_eq!(stat.busy_ticks, busy, "{line}");
+            assert_eq!(stat.total_ticks, total, "{line}");
+        }
+
+        assert!(parse_stat_line("cpu3 1 2 3").is_err());
+        assert!(parse_stat_line("").is_err());
+    }
+
+    #[test]
+    fn pid_mountinfo_line() {
+        // line, major:minor, fs type, source, mount point, and readonly
+        let cases = [
+            (
+                "28 1 8:0 / / rw,relatime shared:1 - ext4 /dev/sda rw",
+                ("8:0", "ext4", "/dev/sda", "/", false),
+            ),
+            // no optional field
+            (
+                "30 28 8:1 / /boot ro,relatime - vfat /dev/sda1 rw,fmask=0022",
+                ("8:1", "vfat", "/dev/sda1", "/boot", true),
+            ),
+            // multiple optional fields and remounted ro on errors
+            (
+                "31 28 0:42 / /data rw shared:5 master:1 - btrfs /dev/sdb ro,space_cache",
+                ("0:42", "btrfs", "/dev/sdb", "/data", true),
+            ),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_pid_mountinfo_line(line).unwrap(), expected, "{line}");
+        }
+
+        let bad_lines = [
+            "",
+            "28 1 8:0 /",
+            "28 1 8:0 / / rw,relatime shared:1 ext4 /dev/sda rw",
+            "28 1 8:0 / / rw,relatime - ext4",
+        ];
+        for line in bad_lines {
+            assert!(parse_pid_mountinfo_line(line).is_err(), "{line}");
+        }
+    }
+
+    #[test]
+    fn fixtures() {
+        let lin = super::super::Linux::with_fixtures("procfs", "sysfs");
+
+        let meminfo = lin.parse_meminfo().unwrap();
+        assert_eq!(meminfo.mem_total_kb, 8041544);
+        assert_eq!(meminfo.mem_avail_kb, 6917452);
+        assert_eq!(meminfo.swap_total_kb, 2097148);
+        assert_eq!(meminfo.swap_free_kb, 2031612);
+
+        // the aggregated cpu line is skipped
+        let stats = lin
+            .parse_stat()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        let cpus: Vec<_> = stats.iter().map(|stat| stat.cpu.as_str()).collect();
+        assert_eq!(cpus, ["cpu0", "cpu1"]);
+        assert_eq!(stats[1].idle_ticks, 68996);
+
+        // proc and tmpfs are skipped
+        let infos = lin
+            .parse_self_mountinfo()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].major_minor, "8:0");
+        assert_eq!(infos[0].mount_source, "/dev/sda");
+        assert_eq!(infos[0].mount_point, "/");
+        assert!(!infos[0].readonly);
+    }
+
+    #[test]
+    fn meminfo_without_swap() {
+        // swap fields are missing without CONFIG_SWAP, and MemAvailable is
+        // not the second field on some kernels
+        let lin = super::super::Linux::with_fixtures("procfs-noswap", "sysfs");
+        let meminfo = lin.parse_meminfo().unwrap();
+        assert_eq!(meminfo.mem_total_kb, 254556);
+        assert_eq!(meminfo.mem_avail_kb, 150316);
+        assert_eq!(meminfo.swap_total_kb, 0);
+        assert_eq!(meminfo.swap_free_kb, 0);
     }
 }
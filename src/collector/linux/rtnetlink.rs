@@ -5,35 +5,72 @@ use anyhow::{Context, Result};
 use neli::{
     attr::Attribute,
     consts::nl::NlmF,
-    consts::rtnl::{Arphrd, Iff, Ifla, RtAddrFamily, RtScope, RtTable, Rta, Rtm, Rtn, Rtprot},
+    consts::rtnl::{
+        Arphrd, Iff, Ifla, IflaInfo, RtAddrFamily, RtScope, RtTable, Rta, Rtm, Rtn, Rtprot,
+    },
     nl::NlPayload,
-    router::synchronous::NlRouterReceiverHandle,
+    router::synchronous::{NlRouter, NlRouterReceiverHandle},
     rtnl::{Ifinfomsg, IfinfomsgBuilder, Rtmsg, RtmsgBuilder},
 };
-use std::net;
+use std::{collections, net};
 
 pub(super) struct Link {
+    pub index: i32,
     pub name: String,
+    pub kind: Option<String>,
+    pub mtu: u32,
     pub admin_up: bool,
     pub operstate: u8,
+    pub carrier_up_count: u32,
     pub rx: u64,
     pub tx: u64,
+    pub multicast: u64,
+    pub collisions: u64,
+}
+
+// IF_OPER_* from linux/if.h
+pub(super) fn operstate_name(operstate: u8) -> &'static str {
+    match operstate {
+        1 => "notpresent",
+        2 => "down",
+        3 => "lowerlayerdown",
+        4 => "testing",
+        5 => "dormant",
+        6 => "up",
+        _ => "unknown",
+    }
 }
 
 fn parse_get_link_response(resp: &Ifinfomsg) -> Option<Link> {
+    let index = *resp.ifi_index();
     let admin_up = resp.ifi_flags().contains(Iff::UP);
 
     let mut name = None;
+    let mut kind = None;
+    let mut mtu = None;
     let mut operstate = None;
+    let mut carrier_up_count = None;
     let mut stats64 = None;
     for attr in resp.rtattrs().iter() {
         match attr.rta_type() {
             Ifla::Ifname => {
                 name = attr.get_payload_as_with_len::<String>().ok();
             }
+            Ifla::Linkinfo => {
+                kind = attr.get_attr_handle::<IflaInfo>().ok().and_then(|info| {
+                    info.get_attr_payload_as_with_len::<String>(IflaInfo::Kind)
+                        .ok()
+                });
+            }
+            Ifla::Mtu => {
+                mtu = attr.get_payload_as::<u32>().ok();
+            }
             Ifla::Operstate => {
                 operstate = attr.get_payload_as::<u8>().ok();
             }
+            Ifla::CarrierUpCount => {
+                carrier_up_count = attr.get_payload_as::<u32>().ok();
+            }
             Ifla::Stats64 => {
                 stats64 = Some(attr.payload().as_ref());
             }
@@ -41,23 +78,37 @@ fn parse_get_link_response(resp: &Ifinfomsg) -> Option<Link> {
         }
     }
 
+    let mtu = mtu.unwrap_or(0);
     let operstate = operstate.unwrap_or(0);
+    let carrier_up_count = carrier_up_count.unwrap_or(0);
     let mut rx = 0;
     let mut tx = 0;
+    let mut multicast = 0;
+    let mut collisions = 0;
     if let Some(stats64) = stats64 {
         // struct rtnl_link_stats64
         if stats64.len() >= 32 {
             rx = u64::from_ne_bytes(stats64[16..24].try_into().unwrap());
             tx = u64::from_ne_bytes(stats64[24..32].try_into().unwrap());
         }
+        if stats64.len() >= 80 {
+            multicast = u64::from_ne_bytes(stats64[64..72].try_into().unwrap());
+            collisions = u64::from_ne_bytes(stats64[72..80].try_into().unwrap());
+        }
     }
 
     name.map(|name| Link {
+        index,
         name,
+        kind,
+        mtu,
         admin_up,
         operstate,
+        carrier_up_count,
         rx,
         tx,
+        multicast,
+        collisions,
     })
 }
 
@@ -121,6 +172,24 @@ fn parse_get_route_response(resp: &Rtmsg) -> Option<net::SocketAddr> {
         })
 }
 
+// RT_TABLE_* from linux/rtnetlink.h
+pub(super) fn route_table_name(table: u32) -> String {
+    match table {
+        253 => "default".to_string(),
+        254 => "main".to_string(),
+        255 => "local".to_string(),
+        _ => table.to_string(),
+    }
+}
+
+pub(super) fn route_family_name(family: u8) -> &'static str {
+    match RtAddrFamily::from(family) {
+        RtAddrFamily::Inet => "inet",
+        RtAddrFamily::Inet6 => "inet6",
+        _ => "unspec",
+    }
+}
+
 pub(super) struct RouteIter {
     recv: NlRouterReceiverHandle<Rtm, Rtmsg>,
 }
@@ -144,6 +213,12 @@ impl Iterator for RouteIter {
 }
 
 impl super::Linux {
+    fn rt_sock(&self) -> Result<&NlRouter> {
+        self.rt_sock
+            .as_ref()
+            .context("rtnetlink socket is unavailable")
+    }
+
     pub(super) fn parse_links(&self) -> Result<LinkIter> {
         let req = IfinfomsgBuilder::default()
             .ifi_family(RtAddrFamily::Unspecified)
@@ -151,7 +226,7 @@ impl super::Linux {
             .ifi_index(0)
             .build()?;
         let recv: NlRouterReceiverHandle<Rtm, Ifinfomsg> = self
-            .rt_sock
+            .rt_sock()?
             .send(Rtm::Getlink, NlmF::DUMP, NlPayload::Payload(req))
             .context("failed to send to rtnetlink")?;
 
@@ -170,10 +245,132 @@ impl super::Linux {
             .rtm_type(Rtn::Unspec)
             .build()?;
         let recv: NlRouterReceiverHandle<Rtm, Rtmsg> = self
-            .rt_sock
+            .rt_sock()?
             .send(Rtm::Getroute, NlmF::DUMP, NlPayload::Payload(req))
             .context("failed to send to rtnetlink")?;
 
         Ok(RouteIter { recv })
     }
+
+    // count routes of all tables by (table, family)
+    pub(super) fn parse_route_count(&self) -> Result<collections::BTreeMap<(u32, u8), u64>> {
+        let req = RtmsgBuilder::default()
+            .rtm_family(RtAddrFamily::Unspecified)
+            .rtm_dst_len(0)
+            .rtm_src_len(0)
+            .rtm_tos(0)
+            .rtm_table(RtTable::Unspec)
+            .rtm_protocol(Rtprot::Unspec)
+            .rtm_scope(RtScope::Universe)
+            .rtm_type(Rtn::Unspec)
+            .build()?;
+        let mut recv: NlRouterReceiverHandle<Rtm, Rtmsg> = self
+            .rt_sock()?
+            .send(Rtm::Getroute, NlmF::DUMP, NlPayload::Payload(req))
+            .context("failed to send to rtnetlink")?;
+
+        let mut counts = collections::BTreeMap::new();
+        while let Some(nlmsg) = recv.next_typed::<Rtm, Rtmsg>() {
+            let nlmsg = nlmsg.context("failed to recv from rtnetlink")?;
+            let Some(resp) = nlmsg.get_payload() else {
+                continue;
+            };
+
+            // RTA_TABLE is authoritative when the table id exceeds u8
+            let table = resp
+                .rtattrs()
+                .iter()
+                .find(|attr| *attr.rta_type() == Rta::Table)
+                .and_then(|attr| attr.get_payload_as::<u32>().ok())
+                .unwrap_or_else(|| u8::from(*resp.rtm_table()) as u32);
+            let family = u8::from(*resp.rtm_family());
+
+            *counts.entry((table, family)).or_insert(0) += 1;
+        }
+
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use neli::{
+        rtnl::RtattrBuilder,
+        types::{Buffer, RtBuffer},
+    };
+
+    fn route(dst_len: u8, gateway: Option<net::IpAddr>, oif: Option<u32>) -> Rtmsg {
+        let mut rtattrs = RtBuffer::new();
+        if let Some(gateway) = gateway {
+            let octets = match gateway {
+                net::IpAddr::V4(v4) => v4.octets().to_vec(),
+                net::IpAddr::V6(v6) => v6.octets().to_vec(),
+            };
+            let attr = RtattrBuilder::default()
+                .rta_type(Rta::Gateway)
+                .rta_payload(Buffer::from(octets))
+                .build()
+                .unwrap();
+            rtattrs.push(attr);
+        }
+        if let Some(oif) = oif {
+            let attr = RtattrBuilder::default()
+                .rta_type(Rta::Oif)
+                .rta_payload(oif)
+                .build()
+                .unwrap();
+            rtattrs.push(attr);
+        }
+
+        RtmsgBuilder::default()
+            .rtm_family(match gateway {
+                Some(net::IpAddr::V4(_)) => RtAddrFamily::Inet,
+                _ => RtAddrFamily::Inet6,
+            })
+            .rtm_dst_len(dst_len)
+            .rtm_src_len(0)
+            .rtm_tos(0)
+            .rtm_table(RtTable::Main)
+            .rtm_protocol(Rtprot::Unspec)
+            .rtm_scope(RtScope::Universe)
+            .rtm_type(Rtn::Unicast)
+            .rtattrs(rtattrs)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn get_route_response() {
+        let v4: net::IpAddr = "192.168.1.1".parse().unwrap();
+        let link_local: net::Ipv6Addr = "fe80::1".parse().unwrap();
+        let global: net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+
+        // the scope id of a link-local gateway is the oif
+        let cases = [
+            (
+                route(0, Some(v4), Some(2)),
+                Some(net::SocketAddr::new(v4, 0)),
+            ),
+            (
+                route(0, Some(link_local.into()), Some(3)),
+                Some(net::SocketAddrV6::new(link_local, 0, 0, 3).into()),
+            ),
+            (
+                route(0, Some(link_local.into()), None),
+                Some(net::SocketAddrV6::new(link_local, 0, 0, 0).into()),
+            ),
+            (
+                route(0, Some(global.into()), Some(3)),
+                Some(net::SocketAddrV6::new(global, 0, 0, 0).into()),
+            ),
+            // not a default route
+            (route(64, Some(global.into()), Some(3)), None),
+            // no gateway for a point-to-point default route
+            (route(0, None, Some(4)), None),
+        ];
+        for (resp, expected) in cases {
+            assert_eq!(parse_get_route_response(&resp), expected);
+        }
+    }
 }
@@ -0,0 +1,19 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use anyhow::Result;
+
+impl super::Linux {
+    pub(super) fn parse_sysctl(&self, key: &str) -> Result<Option<i64>> {
+        // as in sysctl(8), dots are literal when slashes are used as separators
+        let file = if key.contains('/') {
+            key.to_string()
+        } else {
+            key.replace('.', "/")
+        };
+        let val = super::read_string(self.procfs_path.join("sys").join(file))?;
+
+        // only single integer values are supported
+        Ok(val.parse().ok())
+    }
+}
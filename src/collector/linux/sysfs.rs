@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: MIT
 
 use anyhow::{Context, Result, anyhow};
-use std::{fs, io::BufRead};
+use std::{fs, io::BufRead, path};
 
 #[derive(Default)]
 pub(super) struct IoStats {
@@ -15,9 +15,27 @@ pub(super) struct CpuFreq {
     pub cur_freq: u64,
 }
 
+pub(super) struct ZramMmStat {
+    pub name: String,
+    pub orig_data_size: u64,
+    pub compr_data_size: u64,
+}
+
 pub(super) struct ThermalZone {
     pub name: String,
     pub temp: u64,
+    pub passive_trip: Option<u64>,
+}
+
+pub(super) struct Hardware {
+    pub model: String,
+    pub vendor: String,
+}
+
+pub(super) struct Hwmon {
+    pub chip: String,
+    pub pwms: Vec<(String, u64)>,
+    pub disk_temp: Option<(String, u64)>,
 }
 
 fn parse_io_stats_line(line: &str) -> Result<IoStats> {
@@ -49,7 +67,28 @@ fn parse_thermal_zone_device(dir: fs::DirEntry, _id: &str) -> Result<ThermalZone
     let name = super::read_string(type_path)?;
     let temp = super::read_u64(temp_path)?;
 
-    Ok(ThermalZone { name, temp })
+    // the lowest passive trip point is where the kernel starts throttling
+    let mut passive_trip: Option<u64> = None;
+    for idx in 0.. {
+        let trip_type_path = dir_path.join(format!("trip_point_{idx}_type"));
+        let Ok(trip_type) = super::read_string(trip_type_path) else {
+            break;
+        };
+        if trip_type != "passive" {
+            continue;
+        }
+
+        let trip_temp_path = dir_path.join(format!("trip_point_{idx}_temp"));
+        if let Ok(trip_temp) = super::read_u64(trip_temp_path) {
+            passive_trip = Some(passive_trip.map_or(trip_temp, |trip| trip.min(trip_temp)));
+        }
+    }
+
+    Ok(ThermalZone {
+        name,
+        temp,
+        passive_trip,
+    })
 }
 
 pub(super) struct ClassThermalIter {
@@ -78,7 +117,103 @@ impl Iterator for ClassThermalIter {
     }
 }
 
+fn parse_hwmon_disk_name(dir_path: &path::Path) -> Option<String> {
+    // drivetemp's parent is a scsi device with the block device under it,
+    // while nvme's parent is the controller itself
+    let dev_path = dir_path.join("device");
+    let block_name = fs::read_dir(dev_path.join("block"))
+        .ok()
+        .and_then(|mut dir_iter| dir_iter.next())
+        .and_then(|entry| entry.ok())
+        .and_then(|entry| entry.file_name().into_string().ok());
+    if block_name.is_some() {
+        return block_name;
+    }
+
+    fs::read_link(&dev_path)
+        .ok()
+        .and_then(|target| target.file_name()?.to_str().map(str::to_string))
+}
+
+fn parse_hwmon_device(dir: fs::DirEntry) -> Result<Hwmon> {
+    let dir_path = dir.path();
+    let name_path = dir_path.join("name");
+
+    let chip = super::read_string(name_path)?;
+
+    // pwmN is the duty cycle; skip pwmN_enable, pwmN_mode, etc.
+    let mut pwms = Vec::new();
+    for entry in fs::read_dir(&dir_path).with_context(|| format!("failed to open {dir_path:?}"))? {
+        let entry = entry.with_context(|| format!("failed to read {dir_path:?}"))?;
+        let Some(sensor) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        let is_pwm = sensor
+            .strip_prefix("pwm")
+            .is_some_and(|idx| !idx.is_empty() && idx.bytes().all(|c| c.is_ascii_digit()));
+        if !is_pwm {
+            continue;
+        }
+
+        // the file is unreadable when the fan is not under pwm control
+        if let Ok(pwm) = super::read_u64(entry.path()) {
+            pwms.push((sensor, pwm));
+        }
+    }
+    pwms.sort();
+
+    let mut disk_temp = None;
+    if chip == "drivetemp" || chip == "nvme" {
+        // temp1 is the composite temperature for nvme
+        let temp = super::read_u64(dir_path.join("temp1_input"))?;
+        let name = parse_hwmon_disk_name(&dir_path).unwrap_or_else(|| chip.clone());
+        disk_temp = Some((name, temp));
+    }
+
+    Ok(Hwmon {
+        chip,
+        pwms,
+        disk_temp,
+    })
+}
+
+pub(super) struct ClassHwmonIter {
+    dir_iter: fs::ReadDir,
+}
+
+impl Iterator for ClassHwmonIter {
+    type Item = Result<Hwmon>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let dir = match self.dir_iter.next() {
+                Some(Ok(dir)) => dir,
+                Some(Err(err)) => return Some(Err(err).context("failed to read class/hwmon")),
+                None => return None,
+            };
+
+            if dir
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("hwmon"))
+            {
+                return Some(parse_hwmon_device(dir));
+            }
+        }
+    }
+}
+
 impl super::Linux {
+    pub(super) fn parse_class_hwmon(&self) -> Result<Option<ClassHwmonIter>> {
+        // class/hwmon is missing when no driver registers a hwmon device
+        if !self.sysfs_path.join("class/hwmon").exists() {
+            return Ok(None);
+        }
+
+        let dir_iter = self.sysfs_read_dir("class/hwmon")?;
+        Ok(Some(ClassHwmonIter { dir_iter }))
+    }
+
     pub(super) fn parse_class_thermal(&self) -> Result<ClassThermalIter> {
         let dir_iter = self.sysfs_read_dir("class/thermal")?;
         Ok(ClassThermalIter { dir_iter })
@@ -95,6 +230,126 @@ impl super::Linux {
         parse_io_stats_line(&line)
     }
 
+    pub(super) fn parse_zram_mm_stat(&self) -> Result<Vec<ZramMmStat>> {
+        let mut zrams = Vec::new();
+        for dir in self.sysfs_read_dir("block")? {
+            let dir = dir.context("failed to read block")?;
+            let Some(name) = dir
+                .file_name()
+                .to_str()
+                .filter(|name| name.starts_with("zram"))
+                .map(str::to_string)
+            else {
+                continue;
+            };
+
+            // mm_stat is missing before the device is initialized
+            let Ok(line) = super::read_string(dir.path().join("mm_stat")) else {
+                continue;
+            };
+
+            // 0:orig_data_size 1:compr_data_size 2:mem_used_total ...
+            let cols: Vec<&str> = line.split_ascii_whitespace().collect();
+            if cols.len() < 2 {
+                return Err(anyhow!("failed to parse mm_stat"));
+            }
+            let [orig_data_size, compr_data_size] =
+                [cols[0], cols[1]].map(|col| col.parse().unwrap_or(0));
+
+            zrams.push(ZramMmStat {
+                name,
+                orig_data_size,
+                compr_data_size,
+            });
+        }
+        zrams.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(zrams)
+    }
+
+    // returns block device names and the larger of the type A and type B
+    // life time estimates in 10% steps
+    pub(super) fn parse_emmc_life_time(&self) -> Result<Vec<(String, u64)>> {
+        let mut emmcs = Vec::new();
+        // bus/mmc is missing without an mmc host
+        if !self.sysfs_path.join("bus/mmc/devices").exists() {
+            return Ok(emmcs);
+        }
+
+        for dir in self.sysfs_read_dir("bus/mmc/devices")? {
+            let dir = dir.context("failed to read bus/mmc/devices")?;
+            let dir_path = dir.path();
+
+            // life_time is specific to emmc 5.0+
+            let Ok(line) = super::read_string(dir_path.join("life_time")) else {
+                continue;
+            };
+            let Some(estimate) = line
+                .split_ascii_whitespace()
+                .filter_map(|val| u64::from_str_radix(val.trim_start_matches("0x"), 16).ok())
+                .max()
+                .filter(|estimate| *estimate > 0)
+            else {
+                continue;
+            };
+
+            let name = fs::read_dir(dir_path.join("block"))
+                .ok()
+                .and_then(|mut dirs| dirs.next()?.ok())
+                .unwrap_or(dir)
+                .file_name()
+                .to_string_lossy()
+                .into_owned();
+            emmcs.push((name, estimate));
+        }
+        emmcs.sort();
+
+        Ok(emmcs)
+    }
+
+    pub(super) fn parse_class_nvme(&self) -> Result<Vec<String>> {
+        let mut nvmes = Vec::new();
+        // class/nvme is missing without the nvme driver
+        if !self.sysfs_path.join("class/nvme").exists() {
+            return Ok(nvmes);
+        }
+
+        for dir in self.sysfs_read_dir("class/nvme")? {
+            let dir = dir.context("failed to read class/nvme")?;
+            nvmes.push(dir.file_name().to_string_lossy().into_owned());
+        }
+        nvmes.sort();
+
+        Ok(nvmes)
+    }
+
+    // device tree on arm and friends, or dmi on x86
+    pub(super) fn parse_hardware(&self) -> Result<Option<Hardware>> {
+        let dt_path = self.sysfs_path.join("firmware/devicetree/base");
+        if dt_path.exists() {
+            // nul-terminated strings
+            let model = super::read_string(dt_path.join("model"))?;
+            let model = model.trim_end_matches('\0').to_string();
+            // the first compatible string is "vendor,board"
+            let vendor = super::read_string(dt_path.join("compatible"))
+                .ok()
+                .and_then(|compat| Some(compat.split_once(',')?.0.to_string()))
+                .unwrap_or_default();
+
+            return Ok(Some(Hardware { model, vendor }));
+        }
+
+        let dmi_path = self.sysfs_path.join("class/dmi/id");
+        if dmi_path.exists() {
+            let model = super::read_string(dmi_path.join("product_name"))?;
+            let vendor = super::read_string(dmi_path.join("sys_vendor")).unwrap_or_default();
+
+            return Ok(Some(Hardware { model, vendor }));
+        }
+
+        Ok(None)
+    }
+
     pub(super) fn parse_cpufreq(&self, cpu: &str) -> Result<CpuFreq> {
         let cur_freq_path = self.sysfs_path.join(format!(
             "devices/system/cpu/{}/cpufreq/scaling_cur_freq",
@@ -105,3 +360,52 @@ impl super::Linux {
         Ok(CpuFreq { cur_freq })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_stats_line() {
+        // line, read bytes, and write bytes
+        let cases = [
+            (
+                "12042 3110 903746 4570 19482 13940 1417864 31222 0 26380 35792 0 0 0 0 2390 960",
+                903746 * 512,
+                1417864 * 512,
+            ),
+            // discard and flush columns are missing on old kernels
+            ("  1 0 8 0 2 0 16 0 0 0 0", 8 * 512, 16 * 512),
+            ("1 0 x 0 2 0 16", 0, 16 * 512),
+        ];
+        for (line, read, write) in cases {
+            let stats = parse_io_stats_line(line).unwrap();
+            assert_eq!(stats.read_bytes, read, "{line}");
+            assert_eq!(stats.write_bytes, write, "{line}");
+        }
+
+        assert!(parse_io_stats_line("1 0 8 0 2 0").is_err());
+        assert!(parse_io_stats_line("").is_err());
+    }
+
+    #[test]
+    fn fixtures() {
+        let lin = super::super::Linux::with_fixtures("procfs", "sysfs");
+
+        // cooling devices are skipped
+        let zones = lin
+            .parse_class_thermal()
+            .unwrap()
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(zones.len(), 1);
+        assert_eq!(zones[0].name, "x86_pkg_temp");
+        assert_eq!(zones[0].temp, 45000);
+        assert_eq!(zones[0].passive_trip, Some(95000));
+
+        let iostats = lin.parse_dev_block("8:0").unwrap();
+        assert_eq!(iostats.read_bytes, 903746 * 512);
+        assert_eq!(iostats.write_bytes, 1417864 * 512);
+        assert!(lin.parse_dev_block("8:16").is_err());
+    }
+}
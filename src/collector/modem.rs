@@ -0,0 +1,180 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, anyhow};
+use std::{sync, time};
+
+const COMMAND_TIMEOUT: time::Duration = time::Duration::from_secs(10);
+
+// indexed by the column order of xdslctl
+const DIRECTIONS: [&str; 2] = ["downstream", "upstream"];
+
+#[derive(Default)]
+struct Stats {
+    timestamp: Option<time::SystemTime>,
+    snr: Option<[f64; 2]>,
+    attenuation: Option<[f64; 2]>,
+    sync_rate: Option<[u64; 2]>,
+}
+
+fn parse_pair(vals: &str) -> Option<[f64; 2]> {
+    let mut vals = vals.split_ascii_whitespace().map(|val| val.parse().ok());
+    Some([vals.next()??, vals.next()??])
+}
+
+// "... Upstream rate = 40000 Kbps, Downstream rate = 100000 Kbps"
+fn parse_rate(line: &str, dir: &str) -> Option<u64> {
+    let (_, rest) = line.split_once(&format!("{dir} rate = "))?;
+    let kbps: u64 = rest.split_ascii_whitespace().next()?.parse().ok()?;
+    Some(kbps * 1000)
+}
+
+// parse the output of "xdslctl info --show" and compatible tools
+fn parse_output(output: &str) -> Stats {
+    let mut stats = Stats::default();
+    for line in output.lines() {
+        if let Some(vals) = line.strip_prefix("SNR (dB):") {
+            stats.snr = parse_pair(vals);
+        } else if let Some(vals) = line.strip_prefix("Attn(dB):") {
+            stats.attenuation = parse_pair(vals);
+        } else if stats.sync_rate.is_none()
+            && (line.starts_with("Bearer:") || line.starts_with("Channel:"))
+        {
+            // the first bearer; "Max:" is the attainable rate instead
+            let down = parse_rate(line, "Downstream");
+            let up = parse_rate(line, "Upstream");
+            if let (Some(down), Some(up)) = (down, up) {
+                stats.sync_rate = Some([down, up]);
+            }
+        }
+    }
+
+    stats
+}
+
+pub(super) struct Modem {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    command: &'static [String],
+    interval: time::Duration,
+    max_staleness: time::Duration,
+    stats: sync::Mutex<Stats>,
+}
+
+impl Modem {
+    pub fn new(
+        errors: sync::Arc<collector::ErrorCounter>,
+        command: &'static [String],
+    ) -> sync::Arc<Self> {
+        let modem = Modem {
+            errors,
+            command,
+            interval: config::get().modem_interval,
+            max_staleness: config::get().max_staleness,
+            stats: Default::default(),
+        };
+        let modem = sync::Arc::new(modem);
+
+        let clone = modem.clone();
+        tokio::task::spawn(async move {
+            clone.task().await;
+        });
+
+        modem
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.stats.lock().unwrap().timestamp.is_some()
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("modem") {
+            return;
+        }
+
+        // stop re-emitting old stats such that the series go stale
+        let stats = self.stats.lock().unwrap();
+        let Some(timestamp) = stats.timestamp.filter(|timestamp| {
+            timestamp
+                .elapsed()
+                .is_ok_and(|age| age <= self.max_staleness)
+        }) else {
+            return;
+        };
+
+        if let Some(snr) = stats.snr {
+            let mut menc = enc.with_info(&metrics.net.dsl_snr, Some(timestamp));
+            for (dir, val) in DIRECTIONS.iter().zip(snr) {
+                menc.write(&[dir], val);
+            }
+        }
+
+        if let Some(attenuation) = stats.attenuation {
+            let mut menc = enc.with_info(&metrics.net.dsl_attenuation, Some(timestamp));
+            for (dir, val) in DIRECTIONS.iter().zip(attenuation) {
+                menc.write(&[dir], val);
+            }
+        }
+
+        if let Some(sync_rate) = stats.sync_rate {
+            let mut menc = enc.with_info(&metrics.net.dsl_sync_rate, Some(timestamp));
+            for (dir, val) in DIRECTIONS.iter().zip(sync_rate) {
+                menc.write(&[dir], val);
+            }
+        }
+    }
+
+    // the command may be slow, so it is run periodically rather than on scrape
+    async fn task(&self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let res = self.run_command().await;
+            self.errors.update("modem", &res);
+            match res {
+                Ok(stats) => *self.stats.lock().unwrap() = stats,
+                Err(err) => self.errors.log(
+                    "modem",
+                    log::Level::Error,
+                    format!("failed to collect modem stats: {err:?}"),
+                ),
+            }
+        }
+    }
+
+    async fn run_command(&self) -> Result<Stats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let (prog, args) = self.command.split_first().context("empty modem command")?;
+        let output = tokio::process::Command::new(prog)
+            .args(args)
+            .kill_on_drop(true)
+            .output();
+        let output = tokio::time::timeout(COMMAND_TIMEOUT, output)
+            .await
+            .with_context(|| format!("timed out running {prog}"))?
+            .with_context(|| format!("failed to run {prog}"))?;
+        if !output.status.success() {
+            return Err(anyhow!("{prog} exited with {}", output.status).into());
+        }
+
+        let stats = parse_output(&String::from_utf8_lossy(&output.stdout));
+        if stats.snr.is_none() && stats.attenuation.is_none() && stats.sync_rate.is_none() {
+            return Err(anyhow!("no line stats in the output of {prog}").into());
+        }
+
+        Ok(Stats {
+            timestamp: Some(timestamp),
+            ..stats
+        })
+    }
+}
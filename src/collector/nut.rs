@@ -0,0 +1,258 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, Result, anyhow};
+use std::{iter, sync, time};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+
+const NUT_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+#[derive(Default)]
+struct Stats {
+    timestamp: Option<time::SystemTime>,
+    battery_charge: Option<f64>,
+    input_voltage: Option<f64>,
+    on_battery: Option<bool>,
+}
+
+// one ups and the upsd serving it
+struct Ups {
+    name: &'static str,
+    addr: &'static str,
+    stats: sync::Mutex<Stats>,
+    up: sync::atomic::AtomicBool,
+}
+
+// VAR <ups> <name> "<value>"
+fn parse_var(line: &str) -> Option<(&str, &str)> {
+    let mut cols = line.splitn(4, ' ');
+    if cols.next()? != "VAR" {
+        return None;
+    }
+    let name = cols.nth(1)?;
+    let val = cols.next()?.strip_prefix('"')?.strip_suffix('"')?;
+
+    Some((name, val))
+}
+
+pub(super) struct Nut {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    upses: Vec<Ups>,
+    refresh_interval: Option<time::Duration>,
+    max_staleness: time::Duration,
+    // bumped on each scrape; the task coalesces pending requests
+    refresh: tokio::sync::watch::Sender<u64>,
+    // the last refresh seq the task has finished
+    refreshed: tokio::sync::watch::Sender<u64>,
+    synchronous: bool,
+}
+
+impl Nut {
+    pub fn new(
+        errors: sync::Arc<collector::ErrorCounter>,
+        upses: &'static [(String, String)],
+    ) -> sync::Arc<Self> {
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        let upses = upses
+            .iter()
+            .map(|(name, addr)| Ups {
+                name,
+                addr,
+                stats: Default::default(),
+                up: sync::atomic::AtomicBool::new(false),
+            })
+            .collect();
+
+        let nut = Nut {
+            errors,
+            upses,
+            refresh_interval: config::get().refresh_interval,
+            max_staleness: config::get().max_staleness,
+            refresh,
+            refreshed,
+            synchronous: config::get().synchronous,
+        };
+        let nut = sync::Arc::new(nut);
+
+        let clone = nut.clone();
+        tokio::task::spawn(async move {
+            clone.task(refresh_rx).await;
+        });
+
+        nut
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.upses
+            .iter()
+            .all(|ups| ups.stats.lock().unwrap().timestamp.is_some())
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("nut") {
+            return;
+        }
+
+        let mut menc = enc.with_info(&metrics.power.ups_up, None);
+        for ups in &self.upses {
+            let up = ups.up.load(sync::atomic::Ordering::Relaxed);
+            menc.write(&[ups.name], up as u64);
+        }
+
+        // stop re-emitting old stats such that the series go stale
+        let stats: Vec<_> = self
+            .upses
+            .iter()
+            .map(|ups| ups.stats.lock().unwrap())
+            .collect();
+        let stats: Vec<_> = iter::zip(&self.upses, &stats)
+            .filter(|(_, stats)| {
+                stats
+                    .timestamp
+                    .is_some_and(|ts| ts.elapsed().is_ok_and(|age| age <= self.max_staleness))
+            })
+            .map(|(ups, stats)| (ups.name, &**stats))
+            .collect();
+
+        // a metric has a single timestamp; use the oldest one
+        let timestamp = stats.iter().filter_map(|(_, stats)| stats.timestamp).min();
+
+        let mut menc = enc.with_info(&metrics.power.battery_charge, timestamp);
+        for (name, stats) in &stats {
+            if let Some(val) = stats.battery_charge {
+                menc.write(&[name], val);
+            }
+        }
+
+        let mut menc = enc.with_info(&metrics.power.input_voltage, timestamp);
+        for (name, stats) in &stats {
+            if let Some(val) = stats.input_voltage {
+                menc.write(&[name], val);
+            }
+        }
+
+        let mut menc = enc.with_info(&metrics.power.on_battery, timestamp);
+        for (name, stats) in &stats {
+            if let Some(val) = stats.on_battery {
+                menc.write(&[name], val as u64);
+            }
+        }
+
+        // in synchronous mode, refreshes are requested before collecting
+        if !self.synchronous {
+            self.request_refresh();
+        }
+    }
+
+    pub fn request_refresh(&self) -> u64 {
+        self.refresh.send_modify(|seq| *seq += 1);
+        *self.refresh.borrow()
+    }
+
+    pub async fn wait_refreshed(&self, seq: u64) {
+        let mut refreshed = self.refreshed.subscribe();
+        let _ = refreshed.wait_for(|refreshed| *refreshed >= seq).await;
+    }
+
+    async fn task(&self, mut refresh: tokio::sync::watch::Receiver<u64>) {
+        loop {
+            let seq = *refresh.borrow_and_update();
+            for ups in &self.upses {
+                let res = tokio::time::timeout(NUT_TIMEOUT, self.parse_stats(ups))
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("timed out querying {}", ups.addr).into()));
+                self.errors.update("nut", &res);
+                ups.up.store(res.is_ok(), sync::atomic::Ordering::Relaxed);
+                match res {
+                    Ok(stats) => *ups.stats.lock().unwrap() = stats,
+                    Err(err) => self.errors.log(
+                        "nut",
+                        log::Level::Error,
+                        format!("failed to collect ups {} stats: {err:?}", ups.name),
+                    ),
+                }
+            }
+
+            self.refreshed.send_replace(seq);
+
+            // refresh on scrape or, if configured, periodically
+            let res = match self.refresh_interval {
+                Some(interval) => tokio::time::timeout(interval, refresh.changed())
+                    .await
+                    .unwrap_or(Ok(())),
+                None => refresh.changed().await,
+            };
+            if res.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn query(&self, ups: &Ups) -> Result<Vec<String>> {
+        let mut sock = tokio::net::TcpStream::connect(ups.addr)
+            .await
+            .with_context(|| format!("failed to connect to {}", ups.addr))?;
+
+        sock.write_all(format!("LIST VAR {}\n", ups.name).as_bytes())
+            .await
+            .context("failed to write to upsd")?;
+
+        // BEGIN LIST VAR <ups>, VAR lines, and END LIST VAR <ups>
+        let mut lines = tokio::io::BufReader::new(sock).lines();
+        let mut vars = Vec::new();
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .context("failed to read from upsd")?
+        {
+            if let Some(err) = line.strip_prefix("ERR ") {
+                return Err(anyhow!("upsd responded {err}"));
+            }
+            if line.starts_with("END LIST VAR") {
+                return Ok(vars);
+            }
+            vars.push(line);
+        }
+
+        Err(anyhow!("upsd closed the connection"))
+    }
+
+    async fn parse_stats(&self, ups: &Ups) -> Result<Stats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let lines = self.query(ups).await?;
+
+        let mut stats = Stats {
+            timestamp: Some(timestamp),
+            ..Default::default()
+        };
+        for (name, val) in lines.iter().filter_map(|line| parse_var(line)) {
+            match name {
+                "battery.charge" => {
+                    let charge: f64 = val.parse().context("failed to parse battery.charge")?;
+                    stats.battery_charge = Some(charge / 100.0);
+                }
+                "input.voltage" => {
+                    stats.input_voltage =
+                        Some(val.parse().context("failed to parse input.voltage")?);
+                }
+                // space-separated flags such as "OL CHRG" or "OB LB"
+                "ups.status" => {
+                    stats.on_battery = Some(val.split_ascii_whitespace().any(|flag| flag == "OB"));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(stats)
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, Result, anyhow};
+use std::{fs, path, sync};
+
+struct SelfStat {
+    cpu_ticks: u64,
+    start_ticks: u64,
+}
+
+pub(super) struct Process {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    procfs_path: &'static path::Path,
+    sysconf_user_hz: u64,
+}
+
+fn parse_self_stat_line(line: &str) -> Result<SelfStat> {
+    // 0:pid 1:comm 2:state ... 13:utime 14:stime ... 21:starttime
+    //
+    // comm is in parentheses and can contain spaces
+    let cols: Vec<&str> = line
+        .rsplit_once(')')
+        .map(|(_, rest)| rest.split_ascii_whitespace().collect())
+        .unwrap_or_default();
+    if cols.len() < 20 {
+        return Err(anyhow!("failed to parse self/stat"));
+    }
+
+    let [utime, stime, start_ticks] =
+        [cols[11], cols[12], cols[19]].map(|col| col.parse().unwrap_or(0));
+
+    Ok(SelfStat {
+        cpu_ticks: utime + stime,
+        start_ticks,
+    })
+}
+
+impl Process {
+    pub fn new(errors: sync::Arc<collector::ErrorCounter>) -> Self {
+        Process {
+            errors,
+            procfs_path: &config::get().procfs_path,
+            sysconf_user_hz: crate::libc::sysconf_user_hz(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn with_fixtures(errors: sync::Arc<collector::ErrorCounter>, procfs: &str) -> Self {
+        Process {
+            errors,
+            procfs_path: collector::fixture_path(procfs),
+            sysconf_user_hz: 100,
+        }
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        self.errors.run(filter, "process_stat", "process stat", || {
+            self.collect_stat(metrics, enc)
+        });
+        self.errors
+            .run(filter, "process_status", "process status", || {
+                self.collect_status(metrics, enc)
+            });
+        self.errors.run(filter, "process_fds", "process fds", || {
+            self.collect_fds(metrics, enc)
+        });
+    }
+
+    fn collect_stat(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let stat = self.parse_self_stat()?;
+        let boot_time = self.parse_boot_time()?;
+
+        let user_hz = self.sysconf_user_hz as f64;
+        enc.write(&metrics.process.cpu, stat.cpu_ticks as f64 / user_hz, None);
+        enc.write(
+            &metrics.process.start_time,
+            boot_time as f64 + stat.start_ticks as f64 / user_hz,
+            None,
+        );
+
+        Ok(())
+    }
+
+    fn collect_status(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let rss_kb = self.parse_self_status_rss()?;
+
+        enc.write(&metrics.process.resident_memory, rss_kb * 1024, None);
+
+        Ok(())
+    }
+
+    fn collect_fds(
+        &self,
+        metrics: &collector::Metrics,
+        enc: &mut metric::Encoder,
+    ) -> Result<(), collector::CollectorError> {
+        let path = self.procfs_path.join("self/fd");
+        let fds = fs::read_dir(&path)
+            .with_context(|| format!("failed to open {:?}", path))?
+            .count();
+
+        enc.write(&metrics.process.open_fds, fds, None);
+
+        Ok(())
+    }
+
+    fn procfs_read(&self, file: &str) -> Result<String> {
+        let path = self.procfs_path.join(file);
+        fs::read_to_string(&path).with_context(|| format!("failed to read {:?}", path))
+    }
+
+    fn parse_self_stat(&self) -> Result<SelfStat> {
+        let stat = self.procfs_read("self/stat")?;
+        parse_self_stat_line(&stat)
+    }
+
+    fn parse_self_status_rss(&self) -> Result<u64> {
+        let status = self.procfs_read("self/status")?;
+
+        // VmRSS: value kB
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|val| val.split_ascii_whitespace().next())
+            .and_then(|val| val.parse().ok())
+            .ok_or(anyhow!("failed to parse self/status"))
+    }
+
+    fn parse_boot_time(&self) -> Result<u64> {
+        let stat = self.procfs_read("stat")?;
+
+        stat.lines()
+            .find_map(|line| line.strip_prefix("btime "))
+            .and_then(|val| val.trim().parse().ok())
+            .ok_or(anyhow!("failed to parse btime"))
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, anyhow};
+use http_body_util::BodyExt;
+use hyper::Uri;
+use std::{sync, time};
+
+// the download is cut short after this long to bound the link usage
+const SPEEDTEST_MAX_DURATION: time::Duration = time::Duration::from_secs(15);
+
+struct Stats {
+    timestamp: time::SystemTime,
+    download_bps: f64,
+}
+
+pub(super) struct Speedtest {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    url: &'static Uri,
+    interval: time::Duration,
+    stats: sync::Mutex<Option<Stats>>,
+}
+
+impl Speedtest {
+    pub fn new(errors: sync::Arc<collector::ErrorCounter>, url: &'static Uri) -> sync::Arc<Self> {
+        let speedtest = Speedtest {
+            errors,
+            url,
+            interval: config::get().speedtest_interval,
+            stats: sync::Mutex::new(None),
+        };
+        let speedtest = sync::Arc::new(speedtest);
+
+        let clone = speedtest.clone();
+        tokio::task::spawn(async move {
+            clone.task().await;
+        });
+
+        speedtest
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.stats.lock().unwrap().is_some()
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("speedtest") {
+            return;
+        }
+
+        let stats = self.stats.lock().unwrap();
+        if let Some(stats) = stats.as_ref() {
+            enc.write(
+                &metrics.net.wan_download,
+                stats.download_bps,
+                Some(stats.timestamp),
+            );
+        }
+    }
+
+    // like wanip, this is never refreshed on scrape
+    async fn task(&self) {
+        // the first download is also delayed, such that restarting the
+        // exporter repeatedly does not saturate the link
+        let start = tokio::time::Instant::now() + self.interval;
+        let mut interval = tokio::time::interval_at(start, self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let res = self.measure_download().await;
+            self.errors.update("speedtest", &res);
+            match res {
+                Ok(download_bps) => {
+                    *self.stats.lock().unwrap() = Some(Stats {
+                        timestamp: time::SystemTime::now(),
+                        download_bps,
+                    })
+                }
+                Err(err) => self.errors.log(
+                    "speedtest",
+                    log::Level::Error,
+                    format!("failed to measure download throughput: {err:?}"),
+                ),
+            }
+        }
+    }
+
+    async fn measure_download(&self) -> Result<f64, collector::CollectorError> {
+        let resp = crate::http_client::get().get(self.url).await?;
+
+        let start = tokio::time::Instant::now();
+        let deadline = start + SPEEDTEST_MAX_DURATION;
+        let mut body = resp.into_body();
+        let mut bytes = 0;
+        // stop at the end of the body or at the deadline, whichever is first
+        while let Ok(frame) = tokio::time::timeout_at(deadline, body.frame()).await {
+            let Some(frame) = frame else {
+                break;
+            };
+            let frame = frame.context("failed to receive body")?;
+            if let Some(data) = frame.data_ref() {
+                bytes += data.len();
+            }
+        }
+
+        let elapsed = start.elapsed().as_secs_f64();
+        if bytes == 0 || elapsed == 0.0 {
+            return Err(anyhow!("nothing downloaded from {}", self.url).into());
+        }
+
+        Ok(bytes as f64 * 8.0 / elapsed)
+    }
+}
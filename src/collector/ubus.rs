@@ -0,0 +1,461 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, Result, anyhow};
+use serde_json::{Map, Value};
+use std::{path, sync, time};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const UBUS_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+// enum ubus_msg_type of libubus
+const UBUS_MSG_HELLO: u8 = 0;
+const UBUS_MSG_STATUS: u8 = 1;
+const UBUS_MSG_DATA: u8 = 2;
+const UBUS_MSG_LOOKUP: u8 = 4;
+const UBUS_MSG_INVOKE: u8 = 5;
+
+// enum ubus_msg_attr of libubus
+const UBUS_ATTR_STATUS: u8 = 1;
+const UBUS_ATTR_OBJPATH: u8 = 2;
+const UBUS_ATTR_OBJID: u8 = 3;
+const UBUS_ATTR_METHOD: u8 = 4;
+const UBUS_ATTR_DATA: u8 = 7;
+
+// enum blobmsg_type of libubox
+const BLOBMSG_TYPE_ARRAY: u8 = 1;
+const BLOBMSG_TYPE_TABLE: u8 = 2;
+const BLOBMSG_TYPE_STRING: u8 = 3;
+const BLOBMSG_TYPE_INT64: u8 = 4;
+const BLOBMSG_TYPE_INT32: u8 = 5;
+const BLOBMSG_TYPE_INT16: u8 = 6;
+const BLOBMSG_TYPE_INT8: u8 = 7;
+const BLOBMSG_TYPE_DOUBLE: u8 = 8;
+
+struct Interface {
+    name: String,
+    up: bool,
+    uptime: u64,
+}
+
+struct Stats {
+    timestamp: time::SystemTime,
+    interfaces: Vec<Interface>,
+    // hostapd interface and associated station count
+    wireless_clients: Vec<(String, u64)>,
+}
+
+fn put_attr(buf: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    // id in bits 24..31 and the unpadded length, including the header, in
+    // bits 0..24
+    let len = 4 + payload.len() as u32;
+    buf.extend_from_slice(&((id as u32) << 24 | len).to_be_bytes());
+    buf.extend_from_slice(payload);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+fn put_string_attr(buf: &mut Vec<u8>, id: u8, s: &str) {
+    let mut payload = s.as_bytes().to_vec();
+    payload.push(0);
+    put_attr(buf, id, &payload);
+}
+
+// returns the extended flag, id, and payload of each attr
+fn parse_attrs(mut data: &[u8]) -> impl Iterator<Item = (bool, u8, &[u8])> {
+    std::iter::from_fn(move || {
+        let hdr = u32::from_be_bytes(data.get(..4)?.try_into().ok()?);
+        let len = (hdr & 0xffffff) as usize;
+        let payload = data.get(4..len)?;
+        data = data.get(len.next_multiple_of(4)..).unwrap_or_default();
+
+        Some((hdr & (1 << 31) != 0, (hdr >> 24) as u8 & 0x7f, payload))
+    })
+}
+
+fn parse_string(payload: &[u8]) -> String {
+    let s = payload.split(|b| *b == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(s).into_owned()
+}
+
+// converts a blobmsg array or table to json
+fn parse_blobmsg(data: &[u8], table: bool) -> Value {
+    let mut map = Map::new();
+    let mut array = Vec::new();
+    for (_, ty, payload) in parse_attrs(data) {
+        // name length, nul-terminated name, and padding
+        let Some(name_len) = payload
+            .get(..2)
+            .map(|len| u16::from_be_bytes([len[0], len[1]]))
+        else {
+            continue;
+        };
+        let name = parse_string(payload.get(2..).unwrap_or_default());
+        let hdr_len = (2 + name_len as usize + 1).next_multiple_of(4);
+        let val = payload.get(hdr_len..).unwrap_or_default();
+
+        let val = match ty {
+            BLOBMSG_TYPE_ARRAY => parse_blobmsg(val, false),
+            BLOBMSG_TYPE_TABLE => parse_blobmsg(val, true),
+            BLOBMSG_TYPE_STRING => Value::from(parse_string(val)),
+            BLOBMSG_TYPE_INT64 => val.get(..8).map_or(Value::Null, |v| {
+                u64::from_be_bytes(v.try_into().unwrap()).into()
+            }),
+            BLOBMSG_TYPE_INT32 => val.get(..4).map_or(Value::Null, |v| {
+                u32::from_be_bytes(v.try_into().unwrap()).into()
+            }),
+            BLOBMSG_TYPE_INT16 => val
+                .get(..2)
+                .map_or(Value::Null, |v| u16::from_be_bytes([v[0], v[1]]).into()),
+            // also bool
+            BLOBMSG_TYPE_INT8 => val.first().map_or(Value::Null, |v| (*v).into()),
+            BLOBMSG_TYPE_DOUBLE => val.get(..8).map_or(Value::Null, |v| {
+                f64::from_be_bytes(v.try_into().unwrap()).into()
+            }),
+            _ => Value::Null,
+        };
+
+        if table {
+            map.insert(name, val);
+        } else {
+            array.push(val);
+        }
+    }
+
+    if table {
+        Value::Object(map)
+    } else {
+        Value::Array(array)
+    }
+}
+
+struct Conn {
+    sock: tokio::net::UnixStream,
+    seq: u16,
+}
+
+impl Conn {
+    async fn connect(path: &path::Path) -> Result<Self> {
+        let sock = tokio::net::UnixStream::connect(path)
+            .await
+            .with_context(|| format!("failed to connect to {path:?}"))?;
+
+        let mut conn = Conn { sock, seq: 0 };
+        // ubusd greets with our client id
+        let (ty, _, _) = conn.recv().await?;
+        if ty != UBUS_MSG_HELLO {
+            return Err(anyhow!("unexpected ubus message {ty}"));
+        }
+
+        Ok(conn)
+    }
+
+    async fn send(&mut self, ty: u8, peer: u32, attrs: &[u8]) -> Result<u16> {
+        self.seq = self.seq.wrapping_add(1);
+
+        // version, type, seq, peer, and the attrs in a container attr
+        let mut buf = vec![0, ty];
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.extend_from_slice(&peer.to_be_bytes());
+        put_attr(&mut buf, 0, attrs);
+
+        self.sock
+            .write_all(&buf)
+            .await
+            .context("failed to write to ubus")?;
+
+        Ok(self.seq)
+    }
+
+    // returns the type, seq, and attrs of a message
+    async fn recv(&mut self) -> Result<(u8, u16, Vec<u8>)> {
+        let mut hdr = [0; 12];
+        self.sock
+            .read_exact(&mut hdr)
+            .await
+            .context("failed to read from ubus")?;
+
+        let seq = u16::from_be_bytes([hdr[2], hdr[3]]);
+        let len = u32::from_be_bytes([hdr[8], hdr[9], hdr[10], hdr[11]]) & 0xffffff;
+        let mut attrs = vec![0; (len as usize).saturating_sub(4)];
+        self.sock
+            .read_exact(&mut attrs)
+            .await
+            .context("failed to read from ubus")?;
+
+        Ok((hdr[1], seq, attrs))
+    }
+
+    // returns the attrs of the data replies
+    async fn request(&mut self, ty: u8, peer: u32, attrs: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let seq = self.send(ty, peer, attrs).await?;
+
+        // data replies are followed by a status reply
+        let mut replies = Vec::new();
+        loop {
+            let (ty, reply_seq, attrs) = self.recv().await?;
+            if reply_seq != seq {
+                continue;
+            }
+
+            match ty {
+                UBUS_MSG_DATA => replies.push(attrs),
+                UBUS_MSG_STATUS => {
+                    let status = parse_attrs(&attrs)
+                        .find(|(_, id, _)| *id == UBUS_ATTR_STATUS)
+                        .and_then(|(_, _, payload)| payload.get(..4))
+                        .map_or(0, |v| u32::from_be_bytes(v.try_into().unwrap()));
+                    if status != 0 {
+                        return Err(anyhow!("ubus responded status {status}"));
+                    }
+
+                    return Ok(replies);
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // returns object paths and ids; a trailing '*' matches any suffix
+    async fn lookup(&mut self, path: &str) -> Result<Vec<(String, u32)>> {
+        let mut attrs = Vec::new();
+        put_string_attr(&mut attrs, UBUS_ATTR_OBJPATH, path);
+
+        let objs = self
+            .request(UBUS_MSG_LOOKUP, 0, &attrs)
+            .await?
+            .iter()
+            .filter_map(|reply| {
+                let mut obj_path = None;
+                let mut obj_id = None;
+                for (_, id, payload) in parse_attrs(reply) {
+                    match id {
+                        UBUS_ATTR_OBJPATH => obj_path = Some(parse_string(payload)),
+                        UBUS_ATTR_OBJID => {
+                            obj_id = payload
+                                .get(..4)
+                                .map(|v| u32::from_be_bytes(v.try_into().unwrap()))
+                        }
+                        _ => (),
+                    }
+                }
+                Some((obj_path?, obj_id?))
+            })
+            .collect();
+
+        Ok(objs)
+    }
+
+    async fn invoke(&mut self, obj_id: u32, method: &str) -> Result<Value> {
+        let mut attrs = Vec::new();
+        put_attr(&mut attrs, UBUS_ATTR_OBJID, &obj_id.to_be_bytes());
+        put_string_attr(&mut attrs, UBUS_ATTR_METHOD, method);
+        put_attr(&mut attrs, UBUS_ATTR_DATA, &[]);
+
+        let replies = self.request(UBUS_MSG_INVOKE, obj_id, &attrs).await?;
+        let data = replies
+            .iter()
+            .find_map(|reply| {
+                parse_attrs(reply)
+                    .find(|(_, id, _)| *id == UBUS_ATTR_DATA)
+                    .map(|(_, _, payload)| parse_blobmsg(payload, true))
+            })
+            .ok_or_else(|| anyhow!("no data from {method}"))?;
+
+        Ok(data)
+    }
+}
+
+pub(super) struct Ubus {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    path: &'static path::Path,
+    refresh_interval: Option<time::Duration>,
+    max_staleness: time::Duration,
+    stats: sync::Mutex<Option<Stats>>,
+    up: sync::atomic::AtomicBool,
+    // bumped on each scrape; the task coalesces pending requests
+    refresh: tokio::sync::watch::Sender<u64>,
+    // the last refresh seq the task has finished
+    refreshed: tokio::sync::watch::Sender<u64>,
+    synchronous: bool,
+}
+
+impl Ubus {
+    pub fn new(
+        errors: sync::Arc<collector::ErrorCounter>,
+        path: &'static path::Path,
+    ) -> sync::Arc<Self> {
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        let ubus = Ubus {
+            errors,
+            path,
+            refresh_interval: config::get().refresh_interval,
+            max_staleness: config::get().max_staleness,
+            stats: sync::Mutex::new(None),
+            up: sync::atomic::AtomicBool::new(false),
+            refresh,
+            refreshed,
+            synchronous: config::get().synchronous,
+        };
+        let ubus = sync::Arc::new(ubus);
+
+        let clone = ubus.clone();
+        tokio::task::spawn(async move {
+            clone.task(refresh_rx).await;
+        });
+
+        ubus
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.stats.lock().unwrap().is_some()
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("ubus") {
+            return;
+        }
+
+        let up = self.up.load(sync::atomic::Ordering::Relaxed);
+        enc.write(&metrics.net.ubus_up, up as u64, None);
+
+        // stop re-emitting old stats such that the series go stale
+        let stats = self.stats.lock().unwrap();
+        let stats = stats.as_ref().filter(|stats| {
+            stats
+                .timestamp
+                .elapsed()
+                .is_ok_and(|age| age <= self.max_staleness)
+        });
+        if let Some(stats) = stats {
+            let mut menc = enc.with_info(&metrics.net.netifd_interface_up, Some(stats.timestamp));
+            for iface in &stats.interfaces {
+                menc.write(&[&iface.name], iface.up as u64);
+            }
+
+            let mut menc =
+                enc.with_info(&metrics.net.netifd_interface_uptime, Some(stats.timestamp));
+            for iface in stats.interfaces.iter().filter(|iface| iface.up) {
+                menc.write(&[&iface.name], iface.uptime);
+            }
+
+            let mut menc = enc.with_info(&metrics.net.wireless_clients, Some(stats.timestamp));
+            for (iface, count) in &stats.wireless_clients {
+                menc.write(&[iface], count);
+            }
+        }
+
+        // in synchronous mode, refreshes are requested before collecting
+        if !self.synchronous {
+            self.request_refresh();
+        }
+    }
+
+    pub fn request_refresh(&self) -> u64 {
+        self.refresh.send_modify(|seq| *seq += 1);
+        *self.refresh.borrow()
+    }
+
+    pub async fn wait_refreshed(&self, seq: u64) {
+        let mut refreshed = self.refreshed.subscribe();
+        let _ = refreshed.wait_for(|refreshed| *refreshed >= seq).await;
+    }
+
+    async fn task(&self, mut refresh: tokio::sync::watch::Receiver<u64>) {
+        loop {
+            let seq = *refresh.borrow_and_update();
+            let res = tokio::time::timeout(UBUS_TIMEOUT, self.parse_stats())
+                .await
+                .unwrap_or_else(|_| Err(anyhow!("timed out querying ubus").into()));
+            self.errors.update("ubus", &res);
+            self.up.store(res.is_ok(), sync::atomic::Ordering::Relaxed);
+            match res {
+                Ok(stats) => *self.stats.lock().unwrap() = Some(stats),
+                Err(err) => {
+                    let level = match err {
+                        collector::CollectorError::NotFound(_) => log::Level::Debug,
+                        _ => log::Level::Error,
+                    };
+
+                    self.errors.log(
+                        "ubus",
+                        level,
+                        format!("failed to collect ubus stats: {err:?}"),
+                    );
+                }
+            }
+
+            self.refreshed.send_replace(seq);
+
+            // refresh on scrape or, if configured, periodically
+            let res = match self.refresh_interval {
+                Some(interval) => tokio::time::timeout(interval, refresh.changed())
+                    .await
+                    .unwrap_or(Ok(())),
+                None => refresh.changed().await,
+            };
+            if res.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn parse_stats(&self) -> Result<Stats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let mut conn = Conn::connect(self.path).await?;
+
+        // netifd logical interfaces
+        let mut interfaces = Vec::new();
+        if let Some((_, obj_id)) = conn.lookup("network.interface").await?.first() {
+            let dump = conn.invoke(*obj_id, "dump").await?;
+            for iface in dump
+                .pointer("/interface")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+            {
+                let Some(name) = iface.pointer("/interface").and_then(Value::as_str) else {
+                    continue;
+                };
+                interfaces.push(Interface {
+                    name: name.to_string(),
+                    up: iface.pointer("/up").and_then(Value::as_u64) == Some(1),
+                    uptime: iface
+                        .pointer("/uptime")
+                        .and_then(Value::as_u64)
+                        .unwrap_or_default(),
+                });
+            }
+        }
+
+        // one hostapd object per wireless interface
+        let mut wireless_clients = Vec::new();
+        for (obj_path, obj_id) in conn.lookup("hostapd.*").await? {
+            let Some(iface) = obj_path.strip_prefix("hostapd.") else {
+                continue;
+            };
+            let clients = conn.invoke(obj_id, "get_clients").await?;
+            let count = clients
+                .pointer("/clients")
+                .and_then(Value::as_object)
+                .map_or(0, |clients| clients.len() as u64);
+            wireless_clients.push((iface.to_string(), count));
+        }
+        wireless_clients.sort();
+
+        Ok(Stats {
+            timestamp,
+            interfaces,
+            wireless_clients,
+        })
+    }
+}
@@ -2,106 +2,539 @@
 // SPDX-License-Identifier: MIT
 
 use crate::{collector, config, metric};
-use anyhow::{Context, Result};
-use std::{io, path, sync, time};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use anyhow::{Context, Result, anyhow};
+use std::{cmp, collections, io, iter, net, os::unix::fs::MetadataExt, path, sync, time};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+
+const MIN_RETRY_BACKOFF: time::Duration = time::Duration::from_secs(1);
+const MAX_RETRY_BACKOFF: time::Duration = time::Duration::from_secs(60);
+
+// query types with their own series; the rest are counted as "other" to bound
+// the cardinality
+const QUERY_TYPES: [&str; 20] = [
+    "A", "NS", "CNAME", "SOA", "PTR", "MX", "TXT", "AAAA", "SRV", "NAPTR", "DS", "RRSIG", "NSEC",
+    "DNSKEY", "NSEC3", "TLSA", "SVCB", "HTTPS", "CAA", "ANY",
+];
 
 struct Stats {
     timestamp: time::SystemTime,
     total_num_queries: u64,
     total_num_queries_timed_out: u64,
+    // indexed by QUERY_TYPES followed by "other"; empty without
+    // extended-statistics
+    query_types: Vec<u64>,
+    // zone and upstream server pairs
+    forwards: Vec<(String, String)>,
 }
 
-pub(super) struct Unbound {
+// one unbound daemon and its control socket
+struct Instance {
+    // empty for an unlabelled socket
+    name: &'static str,
     path: &'static path::Path,
     stats: sync::Mutex<Option<Stats>>,
-    notify: tokio::sync::Notify,
+    up: sync::atomic::AtomicBool,
+}
+
+// clients seen in the log of "log-queries: yes"
+struct QueryLog {
+    path: &'static path::Path,
+    window: time::Duration,
+    // the inode and offset of the next line to read
+    ino: u64,
+    offset: u64,
+    // client address to when it was last seen
+    clients: collections::HashMap<net::IpAddr, time::SystemTime>,
+}
+
+impl QueryLog {
+    // "[1700000000] unbound[1234:0] info: 192.168.1.10 example.com. A IN"
+    fn parse_line(line: &str) -> Option<(net::IpAddr, Option<time::SystemTime>)> {
+        let (prefix, msg) = line.split_once(" info: ")?;
+        let addr = msg.split_ascii_whitespace().next()?.parse().ok()?;
+
+        // the epoch timestamp is absent when log-time-ascii is set
+        let timestamp = prefix
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .and_then(|(secs, _)| secs.parse().ok())
+            .map(|secs| time::UNIX_EPOCH + time::Duration::from_secs(secs));
+
+        Some((addr, timestamp))
+    }
+
+    async fn read(&mut self, max_size: u64) -> Result<()> {
+        let mut file = tokio::fs::File::open(self.path)
+            .await
+            .with_context(|| format!("failed to open {:?}", self.path))?;
+        let meta = file.metadata().await?;
+
+        // start over when the log is rotated or truncated
+        if meta.ino() != self.ino || meta.len() < self.offset {
+            self.ino = meta.ino();
+            self.offset = 0;
+        }
+
+        // skip ahead rather than reading an unbounded amount
+        let mut skip_partial = false;
+        if meta.len() - self.offset > max_size {
+            self.offset = meta.len() - max_size;
+            skip_partial = true;
+        }
+
+        file.seek(io::SeekFrom::Start(self.offset)).await?;
+        let mut data = Vec::new();
+        file.take(meta.len() - self.offset)
+            .read_to_end(&mut data)
+            .await?;
+
+        // leave an incomplete last line for the next read
+        let Some(end) = data.iter().rposition(|b| *b == b'\n') else {
+            return Ok(());
+        };
+        self.offset += end as u64 + 1;
+
+        let now = time::SystemTime::now();
+        let mut lines = String::from_utf8_lossy(&data[..end]).into_owned();
+        if skip_partial {
+            lines = lines.split_once('\n').map_or("", |(_, rest)| rest).into();
+        }
+        for line in lines.lines() {
+            if let Some((addr, timestamp)) = Self::parse_line(line) {
+                let seen = timestamp.unwrap_or(now);
+                let last = self.clients.entry(addr).or_insert(seen);
+                *last = cmp::max(*last, seen);
+            }
+        }
+
+        let window = self.window;
+        self.clients
+            .retain(|_, seen| now.duration_since(*seen).unwrap_or_default() <= window);
+
+        Ok(())
+    }
+}
+
+pub(super) struct Unbound {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    instances: Vec<Instance>,
+    query_log: Option<&'static path::Path>,
+    query_log_window: time::Duration,
+    distinct_clients: sync::Mutex<Option<u64>>,
+    forwards: bool,
+    max_response_size: u64,
+    refresh_interval: Option<time::Duration>,
+    max_staleness: time::Duration,
+    // bumped on each scrape; the task coalesces pending requests
+    refresh: tokio::sync::watch::Sender<u64>,
+    // the last refresh seq the task has finished
+    refreshed: tokio::sync::watch::Sender<u64>,
+    synchronous: bool,
 }
 
 impl Unbound {
-    pub fn new() -> sync::Arc<Self> {
+    pub fn new(errors: sync::Arc<collector::ErrorCounter>) -> sync::Arc<Self> {
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        let instances = config::get()
+            .unbound_sockets
+            .iter()
+            .map(|(name, path)| Instance {
+                name,
+                path,
+                stats: sync::Mutex::new(None),
+                up: sync::atomic::AtomicBool::new(false),
+            })
+            .collect();
+
         let unbound = Unbound {
-            path: &config::get().unbound_socket,
-            stats: sync::Mutex::new(None),
-            notify: tokio::sync::Notify::new(),
+            errors,
+            instances,
+            query_log: config::get().unbound_query_log.as_deref(),
+            query_log_window: config::get().unbound_query_log_window,
+            distinct_clients: sync::Mutex::new(None),
+            forwards: config::get().unbound_forwards,
+            max_response_size: config::get().max_response_size,
+            refresh_interval: config::get().refresh_interval,
+            max_staleness: config::get().max_staleness,
+            refresh,
+            refreshed,
+            synchronous: config::get().synchronous,
         };
         let unbound = sync::Arc::new(unbound);
 
         let clone = unbound.clone();
         tokio::task::spawn(async move {
-            clone.task().await;
+            clone.task(refresh_rx).await;
         });
 
         unbound
     }
 
-    pub fn collect(&self, metrics: &collector::Metrics, enc: &mut metric::Encoder) {
-        if let Some(stats) = &*self.stats.lock().unwrap() {
-            enc.write(
-                &metrics.net.dns_query,
-                stats.total_num_queries,
-                Some(stats.timestamp),
-            );
-            enc.write(
-                &metrics.net.dns_timeout,
-                stats.total_num_queries_timed_out,
-                Some(stats.timestamp),
-            );
-        }
-
-        self.notify.notify_one();
+    // no socket and no refresh task
+    #[cfg(test)]
+    pub fn without_instances(errors: sync::Arc<collector::ErrorCounter>) -> sync::Arc<Self> {
+        let (refresh, _) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        sync::Arc::new(Unbound {
+            errors,
+            instances: Vec::new(),
+            query_log: None,
+            query_log_window: time::Duration::from_secs(3600),
+            distinct_clients: sync::Mutex::new(None),
+            forwards: false,
+            max_response_size: 4096,
+            refresh_interval: None,
+            max_staleness: time::Duration::from_secs(300),
+            refresh,
+            refreshed,
+            synchronous: false,
+        })
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.instances
+            .iter()
+            .all(|inst| inst.stats.lock().unwrap().is_some())
     }
 
-    async fn task(&self) {
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("unbound") {
+            return;
+        }
+
+        let mut menc = enc.with_info(&metrics.net.dns_up, None);
+        for inst in &self.instances {
+            let up = inst.up.load(sync::atomic::Ordering::Relaxed);
+            menc.write(&[inst.name], up as u64);
+        }
+
+        if let Some(count) = *self.distinct_clients.lock().unwrap() {
+            enc.write(&metrics.net.dns_distinct_clients, count, None);
+        }
+
+        // stop re-emitting old stats such that the series go stale
+        let stats: Vec<_> = self
+            .instances
+            .iter()
+            .map(|inst| inst.stats.lock().unwrap())
+            .collect();
+        let stats: Vec<_> = iter::zip(&self.instances, &stats)
+            .filter_map(|(inst, stats)| {
+                let stats = stats.as_ref().filter(|stats| {
+                    stats
+                        .timestamp
+                        .elapsed()
+                        .is_ok_and(|age| age <= self.max_staleness)
+                })?;
+                Some((inst.name, stats))
+            })
+            .collect();
+
+        // a metric has a single timestamp; use the oldest one
+        let timestamp = stats.iter().map(|(_, stats)| stats.timestamp).min();
+
+        let mut menc = enc.with_info(&metrics.net.dns_query, timestamp);
+        for (name, stats) in &stats {
+            menc.write(&[name], stats.total_num_queries);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.dns_timeout, timestamp);
+        for (name, stats) in &stats {
+            menc.write(&[name], stats.total_num_queries_timed_out);
+        }
+
+        let mut menc = enc.with_info(&metrics.net.dns_query_type, timestamp);
+        for (name, stats) in &stats {
+            let types = QUERY_TYPES.iter().chain(iter::once(&"other"));
+            for (ty, val) in iter::zip(types, &stats.query_types) {
+                menc.write(&[name, ty], val);
+            }
+        }
+
+        if self.forwards {
+            let mut menc = enc.with_info(&metrics.net.dns_forward_info, timestamp);
+            for (name, stats) in &stats {
+                for (zone, server) in &stats.forwards {
+                    menc.write_info(&[name, zone, server]);
+                }
+            }
+        }
+
+        // in synchronous mode, refreshes are requested before collecting
+        if !self.synchronous {
+            self.request_refresh();
+        }
+    }
+
+    pub fn request_refresh(&self) -> u64 {
+        self.refresh.send_modify(|seq| *seq += 1);
+        *self.refresh.borrow()
+    }
+
+    pub async fn wait_refreshed(&self, seq: u64) {
+        let mut refreshed = self.refreshed.subscribe();
+        let _ = refreshed.wait_for(|refreshed| *refreshed >= seq).await;
+    }
+
+    async fn task(&self, mut refresh: tokio::sync::watch::Receiver<u64>) {
+        let mut query_log = self.query_log.map(|path| QueryLog {
+            path,
+            window: self.query_log_window,
+            ino: 0,
+            offset: 0,
+            clients: Default::default(),
+        });
+
+        let mut backoff = None;
         loop {
-            match self.parse_stats().await {
-                Ok(stats) => *self.stats.lock().unwrap() = Some(stats),
-                Err(err) => {
-                    let mut level = log::Level::Error;
-                    if let Some(err) = err.downcast_ref::<io::Error>() {
-                        if err.kind() == io::ErrorKind::NotFound {
-                            level = log::Level::Debug;
-                        }
+            let seq = *refresh.borrow_and_update();
+            let mut failed = false;
+            for inst in &self.instances {
+                let res = self.parse_stats(inst).await;
+                self.errors.update("unbound", &res);
+                inst.up.store(res.is_ok(), sync::atomic::Ordering::Relaxed);
+                match res {
+                    Ok(stats) => *inst.stats.lock().unwrap() = Some(stats),
+                    Err(err) => {
+                        let level = match err {
+                            collector::CollectorError::NotFound(_) => log::Level::Debug,
+                            _ => log::Level::Error,
+                        };
+                        failed = true;
+
+                        self.errors.log(
+                            "unbound",
+                            level,
+                            format!("failed to collect unbound stats: {err:?}"),
+                        );
                     }
+                }
+            }
 
-                    log::log!(level, "failed to collect unbound stats: {err:?}");
+            if let Some(query_log) = &mut query_log {
+                let res = query_log
+                    .read(self.max_response_size)
+                    .await
+                    .map_err(collector::CollectorError::from);
+                self.errors.update("unbound_query_log", &res);
+                *self.distinct_clients.lock().unwrap() =
+                    res.as_ref().ok().map(|_| query_log.clients.len() as u64);
+                if let Err(err) = res {
+                    self.errors.log(
+                        "unbound_query_log",
+                        log::Level::Error,
+                        format!("failed to read unbound query log: {err:?}"),
+                    );
                 }
             }
 
-            self.notify.notified().await;
+            // retry without waiting for a scrape, such that a daemon starting
+            // after us is picked up promptly
+            backoff = if failed {
+                Some(backoff.map_or(MIN_RETRY_BACKOFF, |backoff| {
+                    cmp::min(backoff * 2, MAX_RETRY_BACKOFF)
+                }))
+            } else {
+                None
+            };
+
+            self.refreshed.send_replace(seq);
+
+            // refresh on scrape, on retry, or, if configured, periodically
+            let timeout = [self.refresh_interval, backoff].into_iter().flatten().min();
+            let res = match timeout {
+                Some(timeout) => tokio::time::timeout(timeout, refresh.changed())
+                    .await
+                    .unwrap_or(Ok(())),
+                None => refresh.changed().await,
+            };
+            if res.is_err() {
+                break;
+            }
         }
     }
 
-    async fn parse_stats(&self) -> Result<Stats> {
-        let mut sock = tokio::net::UnixStream::connect(&self.path)
+    async fn query(&self, path: &path::Path, cmd: &str) -> Result<String> {
+        let mut sock = tokio::net::UnixStream::connect(path)
             .await
-            .with_context(|| format!("failed to connect to {:?}", self.path))?;
-
-        let timestamp = time::SystemTime::now();
+            .with_context(|| format!("failed to connect to {path:?}"))?;
 
-        sock.write_all("UBCT1 stats_noreset\n".as_bytes())
+        sock.write_all(format!("UBCT1 {cmd}\n").as_bytes())
             .await
             .context("failed to write to unbound")?;
 
         let mut resp = String::new();
-        sock.read_to_string(&mut resp)
+        sock.take(self.max_response_size + 1)
+            .read_to_string(&mut resp)
             .await
             .context("failed to read from unbound")?;
+        if resp.len() as u64 > self.max_response_size {
+            return Err(anyhow!(
+                "unbound response exceeds {} bytes",
+                self.max_response_size
+            ));
+        }
+
+        Ok(resp)
+    }
+
+    fn parse_forwards(resp: &str) -> Vec<(String, String)> {
+        let mut forwards = Vec::new();
+        for line in resp.lines() {
+            // zone class "forward" [+flags...] servers...
+            let mut cols = line.split_ascii_whitespace();
+            let Some(zone) = cols.next() else {
+                continue;
+            };
+            if cols.nth(1) != Some("forward") {
+                continue;
+            }
+
+            for server in cols.filter(|col| !col.starts_with('+')) {
+                forwards.push((zone.to_string(), server.to_string()));
+            }
+        }
+
+        forwards
+    }
+
+    async fn parse_stats(&self, inst: &Instance) -> Result<Stats, collector::CollectorError> {
+        let timestamp = time::SystemTime::now();
+
+        let resp = self.query(inst.path, "stats_noreset").await?;
 
         let mut total_num_queries = 0;
         let mut total_num_queries_timed_out = 0;
+        let mut query_types = Vec::new();
         for line in resp.lines() {
             if let Some(val) = line.strip_prefix("total.num.queries=") {
-                total_num_queries = val.parse()?;
+                total_num_queries = val.parse().context("failed to parse unbound stats")?;
             } else if let Some(val) = line.strip_prefix("total.num.queries_timed_out=") {
-                total_num_queries_timed_out = val.parse()?;
+                total_num_queries_timed_out =
+                    val.parse().context("failed to parse unbound stats")?;
+            } else if let Some((ty, val)) = line
+                .strip_prefix("num.query.type.")
+                .and_then(|rest| rest.split_once('='))
+            {
+                let val: u64 = val.parse().context("failed to parse unbound stats")?;
+                if query_types.is_empty() {
+                    query_types.resize(QUERY_TYPES.len() + 1, 0);
+                }
+                let idx = QUERY_TYPES
+                    .iter()
+                    .position(|known| *known == ty)
+                    .unwrap_or(QUERY_TYPES.len());
+                query_types[idx] += val;
             }
         }
 
+        let forwards = if self.forwards {
+            Self::parse_forwards(&self.query(inst.path, "list_forwards").await?)
+        } else {
+            Vec::new()
+        };
+
         Ok(Stats {
             timestamp,
             total_num_queries,
             total_num_queries_timed_out,
+            query_types,
+            forwards,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(path: &'static path::Path) -> sync::Arc<Unbound> {
+        let (refresh, refresh_rx) = tokio::sync::watch::channel(0);
+        let (refreshed, _) = tokio::sync::watch::channel(0);
+
+        let unbound = sync::Arc::new(Unbound {
+            errors: Default::default(),
+            instances: vec![Instance {
+                name: "",
+                path,
+                stats: sync::Mutex::new(None),
+                up: sync::atomic::AtomicBool::new(false),
+            }],
+            query_log: None,
+            query_log_window: time::Duration::from_secs(3600),
+            distinct_clients: sync::Mutex::new(None),
+            forwards: false,
+            max_response_size: 4096,
+            refresh_interval: None,
+            max_staleness: time::Duration::from_secs(300),
+            refresh,
+            refreshed,
+            synchronous: true,
+        });
+
+        let clone = unbound.clone();
+        tokio::task::spawn(async move {
+            clone.task(refresh_rx).await;
+        });
+
+        unbound
+    }
+
+    // an unbound whose total.num.queries is the number of queries so far
+    async fn serve(
+        listener: tokio::net::UnixListener,
+        queries: sync::Arc<sync::atomic::AtomicU64>,
+    ) {
+        while let Ok((mut sock, _)) = listener.accept().await {
+            let mut buf = [0; 256];
+            let _ = sock.read(&mut buf).await;
+
+            let count = queries.fetch_add(1, sync::atomic::Ordering::Relaxed) + 1;
+            let resp = format!("total.num.queries={count}\ntotal.num.queries_timed_out=0\n");
+            let _ = sock.write_all(resp.as_bytes()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn refresh_on_scrape() {
+        let path = std::env::temp_dir().join(format!("unbound-test-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        let queries = sync::Arc::new(sync::atomic::AtomicU64::new(0));
+        tokio::task::spawn(serve(listener, queries.clone()));
+
+        let unbound = start(Box::leak(path.clone().into_boxed_path()));
+        let scrape =
+            || collector::render(|metrics, enc| unbound.collect(metrics, &Default::default(), enc));
+
+        // each scrape sees the stats queried after it started
+        for _ in 0..3 {
+            let seq = unbound.request_refresh();
+            unbound.wait_refreshed(seq).await;
+
+            let count = queries.load(sync::atomic::Ordering::Relaxed);
+            assert!(scrape().contains(&format!("test_network_dns_query_total {count}\n")));
+        }
+
+        // pending requests are coalesced
+        let last = queries.load(sync::atomic::Ordering::Relaxed);
+        unbound.request_refresh();
+        let seq = unbound.request_refresh();
+        unbound.wait_refreshed(seq).await;
+
+        let count = queries.load(sync::atomic::Ordering::Relaxed);
+        assert!(count > last && count <= last + 2);
+        assert!(scrape().contains(&format!("test_network_dns_query_total {count}\n")));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
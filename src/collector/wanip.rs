@@ -0,0 +1,218 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::{collector, config, metric};
+use anyhow::{Context, Result, anyhow};
+use std::{net, sync, time};
+
+const STUN_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+const STUN_BINDING_RESPONSE: u16 = 0x0101;
+const STUN_MAGIC_COOKIE: u32 = 0x2112a442;
+const STUN_ATTR_MAPPED_ADDRESS: u16 = 0x0001;
+const STUN_ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+
+struct Stats {
+    timestamp: time::SystemTime,
+    ip: net::IpAddr,
+}
+
+fn build_binding_request(txid: &[u8; 12]) -> Vec<u8> {
+    // type, length, magic cookie, and transaction id
+    let mut req = Vec::with_capacity(20);
+    req.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    req.extend_from_slice(&0u16.to_be_bytes());
+    req.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    req.extend_from_slice(txid);
+
+    req
+}
+
+fn parse_mapped_address(val: &[u8], xor: Option<&[u8; 16]>) -> Option<net::IpAddr> {
+    // reserved, family, port, and address
+    let addr = val.get(4..)?;
+    let ip = match (val.get(1)?, addr.len()) {
+        (1, 4) => {
+            let mut octets: [u8; 4] = addr.try_into().ok()?;
+            if let Some(xor) = xor {
+                octets.iter_mut().zip(xor).for_each(|(b, x)| *b ^= x);
+            }
+            net::IpAddr::from(octets)
+        }
+        (2, 16) => {
+            let mut octets: [u8; 16] = addr.try_into().ok()?;
+            if let Some(xor) = xor {
+                octets.iter_mut().zip(xor).for_each(|(b, x)| *b ^= x);
+            }
+            net::IpAddr::from(octets)
+        }
+        _ => return None,
+    };
+
+    Some(ip)
+}
+
+fn parse_binding_response(txid: &[u8; 12], resp: &[u8]) -> Option<net::IpAddr> {
+    if resp.len() < 20
+        || resp[0..2] != STUN_BINDING_RESPONSE.to_be_bytes()
+        || resp[4..8] != STUN_MAGIC_COOKIE.to_be_bytes()
+        || resp[8..20] != txid[..]
+    {
+        return None;
+    }
+
+    // xor-mapped addresses are xor'ed with the magic cookie and the
+    // transaction id
+    let mut xor = [0; 16];
+    xor[..4].copy_from_slice(&resp[4..8]);
+    xor[4..].copy_from_slice(txid);
+
+    let len = u16::from_be_bytes([resp[2], resp[3]]) as usize;
+    let mut attrs = resp.get(20..20 + len)?;
+    let mut mapped = None;
+    while attrs.len() >= 4 {
+        let ty = u16::from_be_bytes([attrs[0], attrs[1]]);
+        let len = u16::from_be_bytes([attrs[2], attrs[3]]) as usize;
+        let val = attrs.get(4..4 + len)?;
+
+        // prefer xor-mapped addresses, which are not mangled by ALGs
+        match ty {
+            STUN_ATTR_XOR_MAPPED_ADDRESS => return parse_mapped_address(val, Some(&xor)),
+            STUN_ATTR_MAPPED_ADDRESS => mapped = parse_mapped_address(val, None),
+            _ => (),
+        }
+
+        // attributes are padded to 4 bytes
+        attrs = attrs
+            .get((4 + len).next_multiple_of(4)..)
+            .unwrap_or_default();
+    }
+
+    mapped
+}
+
+pub(super) struct WanIp {
+    errors: sync::Arc<collector::ErrorCounter>,
+
+    server: &'static str,
+    interval: time::Duration,
+    stats: sync::Mutex<Option<Stats>>,
+    changes: sync::atomic::AtomicU64,
+}
+
+impl WanIp {
+    pub fn new(
+        errors: sync::Arc<collector::ErrorCounter>,
+        server: &'static str,
+    ) -> sync::Arc<Self> {
+        let wanip = WanIp {
+            errors,
+            server,
+            interval: config::get().wanip_interval,
+            stats: sync::Mutex::new(None),
+            changes: sync::atomic::AtomicU64::new(0),
+        };
+        let wanip = sync::Arc::new(wanip);
+
+        let clone = wanip.clone();
+        tokio::task::spawn(async move {
+            clone.task().await;
+        });
+
+        wanip
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.stats.lock().unwrap().is_some()
+    }
+
+    pub fn collect(
+        &self,
+        metrics: &collector::Metrics,
+        filter: &collector::Filter,
+        enc: &mut metric::Encoder,
+    ) {
+        if !filter.matches("wanip") {
+            return;
+        }
+
+        let stats = self.stats.lock().unwrap();
+        if let Some(stats) = stats.as_ref() {
+            enc.with_info(&metrics.net.wan_ip_info, Some(stats.timestamp))
+                .write_info(&[&stats.ip.to_string()]);
+
+            let changes = self.changes.load(sync::atomic::Ordering::Relaxed);
+            enc.write(&metrics.net.wan_ip_change, changes, None);
+        }
+    }
+
+    // unlike other background collectors, this one makes outbound requests
+    // and is never refreshed on scrape
+    async fn task(&self) {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            interval.tick().await;
+
+            let res = self.query_stun().await;
+            self.errors.update("wanip", &res);
+            match res {
+                Ok(ip) => {
+                    let mut stats = self.stats.lock().unwrap();
+                    if stats.as_ref().is_some_and(|stats| stats.ip != ip) {
+                        self.changes.fetch_add(1, sync::atomic::Ordering::Relaxed);
+                    }
+                    *stats = Some(Stats {
+                        timestamp: time::SystemTime::now(),
+                        ip,
+                    });
+                }
+                Err(err) => self.errors.log(
+                    "wanip",
+                    log::Level::Error,
+                    format!("failed to query wan ip: {err:?}"),
+                ),
+            }
+        }
+    }
+
+    async fn query_stun(&self) -> Result<net::IpAddr, collector::CollectorError> {
+        let addr = tokio::net::lookup_host(self.server)
+            .await
+            .with_context(|| format!("failed to resolve {}", self.server))?
+            .next()
+            .ok_or_else(|| anyhow!("no address for {}", self.server))?;
+
+        let bind_addr: net::SocketAddr = if addr.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let sock = tokio::net::UdpSocket::bind(bind_addr)
+            .await
+            .context("failed to bind udp socket")?;
+
+        let now = time::SystemTime::now()
+            .duration_since(time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut txid = [0; 12];
+        txid[..8].copy_from_slice(&now.as_secs().to_ne_bytes());
+        txid[8..].copy_from_slice(&now.subsec_nanos().to_ne_bytes());
+
+        sock.send_to(&build_binding_request(&txid), addr)
+            .await
+            .with_context(|| format!("failed to send to {addr}"))?;
+
+        let mut buf = [0; 512];
+        let len = tokio::time::timeout(STUN_TIMEOUT, sock.recv(&mut buf))
+            .await
+            .with_context(|| format!("timed out querying {addr}"))?
+            .with_context(|| format!("failed to recv from {addr}"))?;
+
+        let ip = parse_binding_response(&txid, &buf[..len])
+            .ok_or_else(|| anyhow!("bad binding response from {addr}"))?;
+
+        Ok(ip)
+    }
+}
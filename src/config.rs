@@ -1,57 +1,641 @@
 // Copyright 2025 Google LLC
 // SPDX-License-Identifier: MIT
 
-use clap::{Arg, ArgAction, Command};
-use std::{path, sync};
+use crate::metric;
+use clap::{Arg, ArgAction, Command, error::ErrorKind};
+use std::{net, path, sync, time};
+
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone)]
+pub struct Cidr {
+    addr: net::IpAddr,
+    prefix_len: u32,
+}
+
+impl Cidr {
+    pub fn contains(&self, addr: net::IpAddr) -> bool {
+        match (self.addr, addr.to_canonical()) {
+            (net::IpAddr::V4(net), net::IpAddr::V4(addr)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len).unwrap_or(0);
+                u32::from(net) & mask == u32::from(addr) & mask
+            }
+            (net::IpAddr::V6(net), net::IpAddr::V6(addr)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len).unwrap_or(0);
+                u128::from(net) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+pub enum DhcpBackend {
+    Kea,
+    Dnsmasq,
+}
 
 pub struct Config {
-    pub debug: bool,
-    pub procfs_path: &'static path::Path,
-    pub sysfs_path: &'static path::Path,
+    pub log_level: log::LevelFilter,
+    pub log_format: LogFormat,
+    pub procfs_path: path::PathBuf,
+    pub sysfs_path: path::PathBuf,
+    pub devfs_path: path::PathBuf,
+    pub netns: Option<String>,
+    pub cpu_usage: bool,
+    pub fs_include_virtual: bool,
+    pub link_rate: bool,
+    pub device_include: Option<regex::Regex>,
+    pub device_exclude: Option<regex::Regex>,
+    pub sysctl_keys: Vec<String>,
+    pub dhcp_backend: DhcpBackend,
     pub kea_socket: path::PathBuf,
-    pub unbound_socket: path::PathBuf,
+    pub kea6_socket: Option<path::PathBuf>,
+    pub kea_lease_file: Option<path::PathBuf>,
+    pub dnsmasq_lease_file: path::PathBuf,
+    pub dnsmasq_dns_addr: Option<net::SocketAddr>,
+    // instance name, which is empty for an unlabelled socket, and path
+    pub unbound_sockets: Vec<(String, path::PathBuf)>,
+    pub unbound_forwards: bool,
+    pub unbound_query_log: Option<path::PathBuf>,
+    pub unbound_query_log_window: time::Duration,
+    pub wanip_stun_server: Option<String>,
+    pub wanip_interval: time::Duration,
+    pub speedtest_url: Option<hyper::Uri>,
+    pub speedtest_interval: time::Duration,
+    pub modem_command: Vec<String>,
+    pub modem_interval: time::Duration,
+    pub ubus_socket: Option<path::PathBuf>,
+    // ups name and upsd address
+    pub nut_upses: Vec<(String, String)>,
+    pub max_response_size: u64,
+    pub refresh_interval: Option<time::Duration>,
+    pub synchronous: bool,
+    pub max_staleness: time::Duration,
     pub hyper_addr: String,
+    pub http_timeout: time::Duration,
+    pub allow_cidrs: Vec<Cidr>,
+    pub enable_lifecycle: bool,
+    pub const_labels: Vec<(String, String)>,
+    pub metric_include: Option<regex::Regex>,
+    pub metric_exclude: Option<regex::Regex>,
+    pub subsys_prefixes: Vec<(String, String)>,
+    pub float_precision: Option<usize>,
+    pub timestamps: metric::Timestamps,
+    pub monotonic_counters: bool,
+    pub state_file: Option<path::PathBuf>,
+}
+
+fn parse_const_label(arg: &str) -> Result<(String, String), String> {
+    let (key, val) = arg
+        .split_once('=')
+        .ok_or(format!("expected KEY=VALUE but got {arg}"))?;
+
+    let mut chars = key.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !key.starts_with("__");
+    if !valid {
+        return Err(format!("invalid label name {key}"));
+    }
+
+    Ok((key.to_string(), val.to_string()))
+}
+
+fn parse_subsys_prefix(arg: &str) -> Result<(String, String), String> {
+    let (subsys, prefix) = arg
+        .split_once('=')
+        .ok_or(format!("expected SUBSYSTEM=PREFIX but got {arg}"))?;
+
+    const SUBSYSTEMS: [&str; 8] = [
+        "cpu",
+        "memory",
+        "filesystem",
+        "thermal",
+        "network",
+        "power",
+        "process",
+        "exporter",
+    ];
+    if !SUBSYSTEMS.contains(&subsys) {
+        return Err(format!("unknown subsystem {subsys}"));
+    }
+
+    let mut chars = prefix.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+    if !valid {
+        return Err(format!("invalid metric name prefix {prefix}"));
+    }
+
+    Ok((subsys.to_string(), prefix.to_string()))
+}
+
+fn parse_cidr(arg: &str) -> Result<Cidr, String> {
+    let (addr, prefix_len) = arg.split_once('/').unwrap_or((arg, ""));
+    let addr: net::IpAddr = addr
+        .parse()
+        .map_err(|_| format!("invalid address in {arg}"))?;
+
+    let max_len = if addr.is_ipv4() { 32 } else { 128 };
+    let prefix_len = if prefix_len.is_empty() {
+        max_len
+    } else {
+        prefix_len
+            .parse()
+            .ok()
+            .filter(|len| *len <= max_len)
+            .ok_or(format!("invalid prefix length in {arg}"))?
+    };
+
+    Ok(Cidr { addr, prefix_len })
+}
+
+// the pattern must match the whole name
+fn parse_regex(arg: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(&format!("^(?:{arg})$")).map_err(|err| err.to_string())
+}
+
+// PATH or NAME=PATH
+fn parse_unbound_socket(arg: &str) -> Result<(String, path::PathBuf), String> {
+    let (name, path) = match arg.split_once('=') {
+        Some((name, path)) if !name.contains('/') => (name, path),
+        _ => ("", arg),
+    };
+    if path.is_empty() {
+        return Err(format!("expected PATH or NAME=PATH but got {arg}"));
+    }
+
+    Ok((name.to_string(), path::PathBuf::from(path)))
+}
+
+// NAME[@HOST[:PORT]] as accepted by upsc
+fn parse_nut_ups(arg: &str) -> Result<(String, String), String> {
+    const NUT_PORT: u16 = 3493;
+
+    let (name, host) = arg.split_once('@').unwrap_or((arg, "localhost"));
+    if name.is_empty() || host.is_empty() {
+        return Err(format!("expected NAME[@HOST[:PORT]] but got {arg}"));
+    }
+
+    let addr = match host.rsplit_once(':') {
+        Some((hostname, port)) if !hostname.contains(':') || hostname.ends_with(']') => {
+            port.parse::<u16>()
+                .map_err(|_| format!("invalid port in {arg}"))?;
+            host.to_string()
+        }
+        // a bare ipv6 address
+        _ if host.contains(':') && !host.starts_with('[') => format!("[{host}]:{NUT_PORT}"),
+        _ => format!("{host}:{NUT_PORT}"),
+    };
+
+    Ok((name.to_string(), addr))
+}
+
+fn parse_http_url(arg: &str) -> Result<hyper::Uri, String> {
+    let url: hyper::Uri = arg.parse().map_err(|_| format!("invalid url {arg}"))?;
+
+    // there is no tls support
+    if url.scheme_str() != Some("http") || url.host().is_none() {
+        return Err(format!("expected an http url but got {arg}"));
+    }
+
+    Ok(url)
 }
 
 fn parse_args() -> Config {
-    let matches = Command::new("home-router-exporter")
+    let mut cmd = Command::new("home-router-exporter")
         .arg(
             Arg::new("debug")
                 .long("debug")
                 .short('d')
-                .action(ArgAction::SetTrue),
+                .action(ArgAction::SetTrue)
+                .conflicts_with("log_level"),
+        )
+        .arg(
+            Arg::new("log_level")
+                .long("log-level")
+                .value_parser(["trace", "debug", "info", "warn", "error"])
+                .default_value("info"),
+        )
+        .arg(
+            Arg::new("log_format")
+                .long("log-format")
+                .value_parser(["text", "json"])
+                .default_value("text"),
         )
         .arg(
             Arg::new("addr")
                 .long("web.listen-address")
                 .default_value("0.0.0.0:9527"),
         )
+        .arg(
+            Arg::new("allow_cidrs")
+                .long("web.allow-cidr")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_parser(parse_cidr),
+        )
+        .arg(
+            Arg::new("enable_lifecycle")
+                .long("web.enable-lifecycle")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(Arg::new("instance_label").long("web.instance-label"))
+        .arg(
+            Arg::new("const_labels")
+                .long("web.const-label")
+                .action(ArgAction::Append)
+                .value_parser(parse_const_label),
+        )
+        .arg(
+            Arg::new("metric_include")
+                .long("web.metric-include")
+                .value_parser(parse_regex),
+        )
+        .arg(
+            Arg::new("metric_exclude")
+                .long("web.metric-exclude")
+                .value_parser(parse_regex),
+        )
+        .arg(
+            Arg::new("subsys_prefixes")
+                .long("metric.subsystem-prefix")
+                .action(ArgAction::Append)
+                .value_parser(parse_subsys_prefix),
+        )
+        .arg(
+            Arg::new("float_precision")
+                .long("metric.float-precision")
+                .value_parser(clap::value_parser!(u64).range(1..=17)),
+        )
+        .arg(
+            Arg::new("timestamps")
+                .long("metric.timestamps")
+                .value_parser(["auto", "always", "never"])
+                .default_value("never"),
+        )
+        .arg(
+            Arg::new("monotonic_counters")
+                .long("metric.monotonic-counters")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("state_file")
+                .long("metric.state-file")
+                .requires("monotonic_counters"),
+        )
+        .arg(
+            Arg::new("http_timeout")
+                .long("http.timeout")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("procfs_path")
+                .long("path.procfs")
+                .default_value("/proc"),
+        )
+        .arg(
+            Arg::new("sysfs_path")
+                .long("path.sysfs")
+                .default_value("/sys"),
+        )
+        .arg(
+            Arg::new("devfs_path")
+                .long("path.devfs")
+                .default_value("/dev"),
+        )
+        .arg(Arg::new("netns").long("collector.netns"))
+        .arg(
+            Arg::new("cpu_usage")
+                .long("collector.cpu.usage")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fs_include_virtual")
+                .long("collector.filesystem.include-virtual")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("link_rate")
+                .long("collector.net.link-rate")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("device_include")
+                .long("collector.netlink.device-include")
+                .value_parser(parse_regex),
+        )
+        .arg(
+            Arg::new("device_exclude")
+                .long("collector.netlink.device-exclude")
+                .value_parser(parse_regex)
+                .default_value("lo|docker[0-9]+|br-[0-9a-f]+|veth.*|virbr[0-9]+(-nic)?"),
+        )
+        .arg(
+            Arg::new("sysctl_keys")
+                .long("collector.sysctl.keys")
+                .action(ArgAction::Append)
+                .value_delimiter(','),
+        )
+        .arg(
+            Arg::new("dhcp_backend")
+                .long("collector.dhcp.backend")
+                .value_parser(["kea", "dnsmasq"])
+                .default_value("kea"),
+        )
         .arg(
             Arg::new("kea_socket")
                 .long("collector.kea.socket")
                 .default_value("/run/kea/kea4-ctrl-socket"),
         )
+        .arg(Arg::new("kea6_socket").long("collector.kea.socket6"))
+        .arg(Arg::new("kea_lease_file").long("collector.kea.lease-file"))
+        .arg(
+            Arg::new("dnsmasq_lease_file")
+                .long("collector.dnsmasq.lease-file")
+                .default_value("/var/lib/misc/dnsmasq.leases"),
+        )
+        .arg(
+            Arg::new("dnsmasq_dns_addr")
+                .long("collector.dnsmasq.dns-address")
+                .value_parser(clap::value_parser!(net::SocketAddr)),
+        )
         .arg(
-            Arg::new("unbound_socket")
+            Arg::new("unbound_sockets")
                 .long("collector.unbound.socket")
+                .action(ArgAction::Append)
+                .value_parser(parse_unbound_socket)
                 .default_value("/run/unbound.ctl"),
         )
-        .get_matches();
+        .arg(
+            Arg::new("unbound_forwards")
+                .long("collector.unbound.forwards")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("unbound_query_log")
+                .long("collector.unbound.query-log")
+                .value_parser(clap::value_parser!(path::PathBuf)),
+        )
+        .arg(
+            Arg::new("unbound_query_log_window")
+                .long("collector.unbound.query-log-window")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("3600"),
+        )
+        .arg(Arg::new("wanip_stun_server").long("collector.wanip.stun-server"))
+        .arg(
+            Arg::new("wanip_interval")
+                .long("collector.wanip.interval")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("300"),
+        )
+        .arg(
+            Arg::new("speedtest_url")
+                .long("collector.speedtest.url")
+                .value_parser(parse_http_url),
+        )
+        .arg(
+            Arg::new("speedtest_interval")
+                .long("collector.speedtest.interval")
+                .value_parser(clap::value_parser!(u64).range(3600..))
+                .default_value("3600"),
+        )
+        .arg(
+            Arg::new("modem_command")
+                .long("collector.modem.command")
+                .value_delimiter(' '),
+        )
+        .arg(
+            Arg::new("modem_interval")
+                .long("collector.modem.interval")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("60"),
+        )
+        .arg(
+            Arg::new("ubus_socket")
+                .long("collector.ubus.socket")
+                .value_parser(clap::value_parser!(path::PathBuf)),
+        )
+        .arg(
+            Arg::new("nut_upses")
+                .long("collector.nut.ups")
+                .action(ArgAction::Append)
+                .value_parser(parse_nut_ups),
+        )
+        .arg(
+            Arg::new("max_response_size")
+                .long("collector.max-response-size")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("4194304"),
+        )
+        .arg(
+            Arg::new("refresh_interval")
+                .long("collector.refresh-interval")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("synchronous")
+                .long("collector.synchronous")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max_staleness")
+                .long("collector.max-staleness")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("300"),
+        );
+    let matches = cmd.get_matches_mut();
 
-    let debug = matches.get_flag("debug");
-    let procfs_path = path::Path::new("/proc");
-    let sysfs_path = path::Path::new("/sys");
+    // --debug is an alias of --log-level=debug
+    let log_level = if matches.get_flag("debug") {
+        log::LevelFilter::Debug
+    } else {
+        matches
+            .get_one::<String>("log_level")
+            .unwrap()
+            .parse()
+            .unwrap()
+    };
+    let log_format = match matches.get_one::<String>("log_format").unwrap().as_str() {
+        "json" => LogFormat::Json,
+        _ => LogFormat::Text,
+    };
+    let procfs_path = path::PathBuf::from(matches.get_one::<String>("procfs_path").unwrap());
+    let sysfs_path = path::PathBuf::from(matches.get_one::<String>("sysfs_path").unwrap());
+    let devfs_path = path::PathBuf::from(matches.get_one::<String>("devfs_path").unwrap());
+    let netns = matches.get_one::<String>("netns").cloned();
+    let cpu_usage = matches.get_flag("cpu_usage");
+    let fs_include_virtual = matches.get_flag("fs_include_virtual");
+    let link_rate = matches.get_flag("link_rate");
+    let device_include = matches.get_one::<regex::Regex>("device_include").cloned();
+    let device_exclude = matches.get_one::<regex::Regex>("device_exclude").cloned();
+    let sysctl_keys = matches
+        .get_many::<String>("sysctl_keys")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let dhcp_backend = match matches.get_one::<String>("dhcp_backend").unwrap().as_str() {
+        "dnsmasq" => DhcpBackend::Dnsmasq,
+        _ => DhcpBackend::Kea,
+    };
     let kea_socket = path::PathBuf::from(matches.get_one::<String>("kea_socket").unwrap());
-    let unbound_socket = path::PathBuf::from(matches.get_one::<String>("unbound_socket").unwrap());
+    let kea6_socket = matches
+        .get_one::<String>("kea6_socket")
+        .map(path::PathBuf::from);
+    let kea_lease_file = matches
+        .get_one::<String>("kea_lease_file")
+        .map(path::PathBuf::from);
+    let dnsmasq_lease_file =
+        path::PathBuf::from(matches.get_one::<String>("dnsmasq_lease_file").unwrap());
+    let dnsmasq_dns_addr = matches
+        .get_one::<net::SocketAddr>("dnsmasq_dns_addr")
+        .copied();
+    let unbound_sockets = matches
+        .get_many::<(String, path::PathBuf)>("unbound_sockets")
+        .unwrap()
+        .cloned()
+        .collect();
+    let unbound_forwards = matches.get_flag("unbound_forwards");
+    let unbound_query_log = matches
+        .get_one::<path::PathBuf>("unbound_query_log")
+        .cloned();
+    let unbound_query_log_window =
+        time::Duration::from_secs(*matches.get_one::<u64>("unbound_query_log_window").unwrap());
+    let wanip_stun_server = matches.get_one::<String>("wanip_stun_server").cloned();
+    let wanip_interval =
+        time::Duration::from_secs(*matches.get_one::<u64>("wanip_interval").unwrap());
+    let speedtest_url = matches.get_one::<hyper::Uri>("speedtest_url").cloned();
+    let speedtest_interval =
+        time::Duration::from_secs(*matches.get_one::<u64>("speedtest_interval").unwrap());
+    // split on spaces; the command is not run by a shell
+    let modem_command = matches
+        .get_many::<String>("modem_command")
+        .unwrap_or_default()
+        .filter(|arg| !arg.is_empty())
+        .cloned()
+        .collect();
+    let modem_interval =
+        time::Duration::from_secs(*matches.get_one::<u64>("modem_interval").unwrap());
+    let ubus_socket = matches.get_one::<path::PathBuf>("ubus_socket").cloned();
+    let nut_upses = matches
+        .get_many::<(String, String)>("nut_upses")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let max_response_size = *matches.get_one::<u64>("max_response_size").unwrap();
+    let refresh_interval = matches
+        .get_one::<u64>("refresh_interval")
+        .map(|secs| time::Duration::from_secs(*secs));
+    let synchronous = matches.get_flag("synchronous");
+    let max_staleness =
+        time::Duration::from_secs(*matches.get_one::<u64>("max_staleness").unwrap());
     let hyper_addr = matches.get_one::<String>("addr").unwrap().clone();
+    let http_timeout = time::Duration::from_secs(*matches.get_one::<u64>("http_timeout").unwrap());
+    let allow_cidrs = matches
+        .get_many::<Cidr>("allow_cidrs")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let enable_lifecycle = matches.get_flag("enable_lifecycle");
+    let instance_label = matches
+        .get_one::<String>("instance_label")
+        .map(|name| ("router".to_string(), name.clone()));
+    let const_labels: Vec<_> = instance_label
+        .into_iter()
+        .chain(
+            matches
+                .get_many::<(String, String)>("const_labels")
+                .unwrap_or_default()
+                .cloned(),
+        )
+        .collect();
+    // other conflicts are checked against the metrics by the collector
+    if matches.contains_id("instance_label")
+        && const_labels.iter().skip(1).any(|(key, _)| key == "router")
+    {
+        cmd.error(
+            ErrorKind::ArgumentConflict,
+            "--web.const-label router=... conflicts with --web.instance-label",
+        )
+        .exit();
+    }
+    let metric_include = matches.get_one::<regex::Regex>("metric_include").cloned();
+    let metric_exclude = matches.get_one::<regex::Regex>("metric_exclude").cloned();
+    let subsys_prefixes = matches
+        .get_many::<(String, String)>("subsys_prefixes")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    // significant digits; values are printed in full by default
+    let float_precision = matches
+        .get_one::<u64>("float_precision")
+        .map(|digits| *digits as usize);
+    let timestamps = match matches.get_one::<String>("timestamps").unwrap().as_str() {
+        "auto" => metric::Timestamps::Auto,
+        "always" => metric::Timestamps::Always,
+        _ => metric::Timestamps::Never,
+    };
+    let monotonic_counters = matches.get_flag("monotonic_counters");
+    let state_file = matches
+        .get_one::<String>("state_file")
+        .map(path::PathBuf::from);
 
     Config {
-        debug,
+        log_level,
+        log_format,
         procfs_path,
         sysfs_path,
+        devfs_path,
+        netns,
+        cpu_usage,
+        fs_include_virtual,
+        link_rate,
+        device_include,
+        device_exclude,
+        sysctl_keys,
+        dhcp_backend,
         kea_socket,
-        unbound_socket,
+        kea6_socket,
+        kea_lease_file,
+        dnsmasq_lease_file,
+        dnsmasq_dns_addr,
+        unbound_sockets,
+        unbound_forwards,
+        unbound_query_log,
+        unbound_query_log_window,
+        wanip_stun_server,
+        wanip_interval,
+        speedtest_url,
+        speedtest_interval,
+        modem_command,
+        modem_interval,
+        ubus_socket,
+        nut_upses,
+        max_response_size,
+        refresh_interval,
+        synchronous,
+        max_staleness,
         hyper_addr,
+        http_timeout,
+        allow_cidrs,
+        enable_lifecycle,
+        const_labels,
+        metric_include,
+        metric_exclude,
+        subsys_prefixes,
+        float_precision,
+        timestamps,
+        monotonic_counters,
+        state_file,
     }
 }
 
@@ -0,0 +1,134 @@
+// Copyright 2025 Google LLC
+// SPDX-License-Identifier: MIT
+
+use crate::config;
+use anyhow::{Context, Result, anyhow};
+use hyper::{Request, Response, Uri, body, client::conn::http1, header};
+use log::debug;
+use std::{collections, sync, time};
+
+type Sender = http1::SendRequest<http_body_util::Empty<body::Bytes>>;
+
+/// An http1 client shared by the collectors that make outbound requests.
+pub struct HttpClient {
+    timeout: time::Duration,
+    // authority to an idle connection
+    idle: sync::Mutex<collections::HashMap<String, Sender>>,
+}
+
+impl HttpClient {
+    fn new() -> Self {
+        HttpClient {
+            timeout: config::get().http_timeout,
+            idle: Default::default(),
+        }
+    }
+
+    async fn connect(host: &str, port: u16) -> Result<Sender> {
+        let stream = tokio::net::TcpStream::connect((host, port))
+            .await
+            .with_context(|| format!("failed to connect to {host}:{port}"))?;
+
+        let io = hyper_util::rt::TokioIo::new(stream);
+        let (sender, conn) = http1::handshake(io).await.context("failed to handshake")?;
+        // the connection task ends when the server closes the connection or
+        // when the sender is dropped
+        tokio::task::spawn(conn);
+
+        Ok(sender)
+    }
+
+    async fn send(&self, url: &Uri) -> Result<Response<body::Incoming>> {
+        let host = url.host().context("url has no host")?;
+        let port = url.port_u16().unwrap_or(80);
+        let authority = url.authority().map_or(host, |auth| auth.as_str());
+        // ipv6 literals keep their brackets
+        let addr = host
+            .strip_prefix('[')
+            .and_then(|host| host.strip_suffix(']'))
+            .unwrap_or(host);
+
+        // a connection is idle once the previous response body is consumed
+        let idle = self
+            .idle
+            .lock()
+            .unwrap()
+            .remove(authority)
+            .filter(|sender| sender.is_ready());
+        let mut sender = match idle {
+            Some(sender) => {
+                debug!("reusing connection to {authority}");
+                sender
+            }
+            None => Self::connect(addr, port).await?,
+        };
+
+        let path = url.path_and_query().map_or("/", |path| path.as_str());
+        let req = Request::get(path)
+            .header(header::HOST, authority)
+            .body(http_body_util::Empty::new())?;
+        let resp = sender
+            .send_request(req)
+            .await
+            .context("failed to send request")?;
+
+        self.idle
+            .lock()
+            .unwrap()
+            .insert(authority.to_string(), sender);
+
+        Ok(resp)
+    }
+
+    // the timeout covers connecting and receiving the headers but not the
+    // body
+    pub async fn get(&self, url: &Uri) -> Result<Response<body::Incoming>> {
+        let resp = tokio::time::timeout(self.timeout, self.send(url))
+            .await
+            .with_context(|| format!("timed out requesting {url}"))??;
+        if !resp.status().is_success() {
+            return Err(anyhow!("unexpected status {}", resp.status()));
+        }
+
+        Ok(resp)
+    }
+}
+
+pub fn get() -> &'static HttpClient {
+    static CLIENT: sync::LazyLock<HttpClient> = sync::LazyLock::new(HttpClient::new);
+    &CLIENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn ipv6_url() {
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::task::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let len = sock.read(&mut buf).await.unwrap();
+            // the host header keeps the brackets
+            let req = String::from_utf8_lossy(&buf[..len]).to_lowercase();
+            let status = if req.contains(&format!("host: [::1]:{port}\r\n")) {
+                "200 OK"
+            } else {
+                "400 Bad Request"
+            };
+            let resp = format!("HTTP/1.1 {status}\r\ncontent-length: 0\r\n\r\n");
+            sock.write_all(resp.as_bytes()).await.unwrap();
+        });
+
+        let client = HttpClient {
+            timeout: time::Duration::from_secs(5),
+            idle: Default::default(),
+        };
+        let url = format!("http://[::1]:{port}/").parse().unwrap();
+        let resp = client.get(&url).await.unwrap();
+        assert_eq!(resp.status(), hyper::StatusCode::OK);
+    }
+}
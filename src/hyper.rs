@@ -3,56 +3,132 @@
 
 use crate::{collector, config};
 use anyhow::{Context, Error, Result};
-use hyper::{Request, Response, body, header, server::conn::http1, service};
+use hyper::{Method, Request, Response, body, header, server::conn::http1, service};
 use log::{debug, error, info};
-use std::{future, net, pin, sync};
+use std::{future, net, pin, str, sync};
+
+fn percent_decode(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        let b = match b {
+            b'+' => b' ',
+            b'%' => {
+                let hex = [iter.next(), iter.next()];
+                match hex {
+                    [Some(hi), Some(lo)] => str::from_utf8(&[hi, lo])
+                        .ok()
+                        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        .unwrap_or(b'?'),
+                    _ => b'?',
+                }
+            }
+            b => b,
+        };
+        bytes.push(b);
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn parse_filter(query: Option<&str>) -> collector::Filter {
+    let collectors = query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|param| param.split_once('='))
+        .filter(|(key, _)| percent_decode(key) == "collect[]")
+        .map(|(_, val)| percent_decode(val))
+        .collect();
+
+    collector::Filter::new(collectors)
+}
+
+// hyper sets content-length from the fully buffered body
+fn metrics_response(buf: String) -> Result<Response<http_body_util::Full<body::Bytes>>> {
+    let resp = Response::builder()
+        .header(header::CONTENT_TYPE, collector::Collector::content_type())
+        .body(http_body_util::Full::from(buf))?;
+
+    Ok(resp)
+}
 
 pub struct HyperTask {
-    collector: collector::Collector,
+    collector: sync::Arc<collector::Collector>,
+    // whether /-/reload is served
+    enable_lifecycle: bool,
+    error_403: Response<http_body_util::Full<body::Bytes>>,
     error_500: Response<http_body_util::Full<body::Bytes>>,
 }
 
 impl HyperTask {
-    fn new(collector: collector::Collector) -> Result<Self> {
+    fn new(collector: sync::Arc<collector::Collector>, enable_lifecycle: bool) -> Result<Self> {
+        let error_403 = Response::builder()
+            .status(403)
+            .body(http_body_util::Full::default())?;
         let error_500 = Response::builder()
             .status(500)
             .body(http_body_util::Full::default())?;
 
         Ok(HyperTask {
             collector,
+            enable_lifecycle,
+            error_403,
             error_500,
         })
     }
 
-    async fn task(&self, stream: tokio::net::TcpStream) {
+    async fn task(&self, stream: tokio::net::TcpStream, allowed: bool) {
         let io = hyper_util::rt::TokioIo::new(stream);
-        let conn = http1::Builder::new().serve_connection(io, self);
+        let res = if allowed {
+            http1::Builder::new().serve_connection(io, self).await
+        } else {
+            let error_403 = self.error_403.clone();
+            let svc =
+                service::service_fn(move |_| future::ready(Ok::<_, Error>(error_403.clone())));
+            http1::Builder::new().serve_connection(io, svc).await
+        };
 
-        if let Err(err) = conn.await {
+        if let Err(err) = res {
             error!("server connection error: {err:?}");
         }
     }
 
-    fn handle_request(
-        &self,
+    async fn handle_request(
+        collector: sync::Arc<collector::Collector>,
+        enable_lifecycle: bool,
         req: Request<body::Incoming>,
     ) -> Result<Response<http_body_util::Full<body::Bytes>>> {
-        match req.uri().path() {
+        let resp = match req.uri().path() {
             "/metrics" => {
-                let buf = self.collector.collect();
+                let filter = parse_filter(req.uri().query());
+                // collectors block on procfs, sysfs, and netlink
+                let buf = tokio::task::spawn_blocking(move || collector.collect(&filter)).await?;
 
-                Response::builder()
-                    .header(header::CONTENT_TYPE, collector::Collector::content_type())
-                    .body(http_body_util::Full::from(buf))
+                metrics_response(buf)?
             }
+            // like prometheus, the lifecycle api is opt-in
+            "/-/reload" if !enable_lifecycle => Response::builder()
+                .status(403)
+                .body(http_body_util::Full::from("lifecycle api is not enabled\n"))?,
+            "/-/reload" if req.method() == Method::POST => {
+                // re-opening sockets blocks as well
+                tokio::task::spawn_blocking(move || collector.reload()).await??;
+
+                Response::builder().body(http_body_util::Full::default())?
+            }
+            "/-/reload" => Response::builder()
+                .status(405)
+                .header(header::ALLOW, "POST")
+                .body(http_body_util::Full::default())?,
             _ => {
                 debug!("incorrect uri {}", req.uri());
                 Response::builder()
                     .status(404)
-                    .body(http_body_util::Full::default())
+                    .body(http_body_util::Full::default())?
             }
-        }
-        .or_else(|_| Ok(self.error_500.clone()))
+        };
+
+        Ok(resp)
     }
 }
 
@@ -63,26 +139,62 @@ impl service::Service<Request<body::Incoming>> for HyperTask {
         pin::Pin<Box<dyn future::Future<Output = Result<Self::Response, Self::Error>> + Send>>;
 
     fn call(&self, req: Request<body::Incoming>) -> Self::Future {
-        let resp = self.handle_request(req);
-        Box::pin(async { resp })
+        let collector = self.collector.clone();
+        let enable_lifecycle = self.enable_lifecycle;
+        let error_500 = self.error_500.clone();
+
+        Box::pin(async move {
+            let resp = Self::handle_request(collector, enable_lifecycle, req)
+                .await
+                .unwrap_or_else(|err| {
+                    error!("failed to handle request: {err:?}");
+                    error_500
+                });
+            Ok(resp)
+        })
     }
 }
 
+// like SocketAddr::from_str but also accepts [fe80::1%eth0]:port
+fn parse_listen_addr(addr: &str) -> Result<net::SocketAddr> {
+    let zoned = addr
+        .strip_prefix('[')
+        .and_then(|addr| addr.split_once("]:"))
+        .and_then(|(ip, port)| ip.split_once('%').map(|(ip, zone)| (ip, zone, port)));
+    let Some((ip, zone, port)) = zoned else {
+        return addr.parse().context("invalid socket address");
+    };
+
+    let ip: net::Ipv6Addr = ip.parse().context("invalid ipv6 address")?;
+    let port = port.parse().context("invalid port")?;
+    // the zone is an interface name or index
+    let scope_id = match zone.parse() {
+        Ok(index) => index,
+        Err(_) => crate::libc::if_nametoindex(zone)?,
+    };
+
+    Ok(net::SocketAddrV6::new(ip, port, 0, scope_id).into())
+}
+
 pub struct Hyper {
     addr: net::SocketAddr,
+    allow_cidrs: &'static [config::Cidr],
     task: sync::Arc<HyperTask>,
 }
 
 impl Hyper {
-    pub fn new(collector: collector::Collector) -> Result<Self> {
+    pub fn new(collector: sync::Arc<collector::Collector>) -> Result<Self> {
         let addr = &config::get().hyper_addr;
-        let addr: net::SocketAddr = addr
-            .parse()
-            .with_context(|| format!("invalid listen address {addr}"))?;
+        let addr =
+            parse_listen_addr(addr).with_context(|| format!("invalid listen address {addr}"))?;
 
-        let task = sync::Arc::new(HyperTask::new(collector)?);
+        let task = sync::Arc::new(HyperTask::new(collector, config::get().enable_lifecycle)?);
 
-        Ok(Hyper { addr, task })
+        Ok(Hyper {
+            addr,
+            allow_cidrs: &config::get().allow_cidrs,
+            task,
+        })
     }
 
     pub async fn run(&self) -> Result<()> {
@@ -93,10 +205,10 @@ impl Hyper {
         info!("listening on {:?}", self.addr);
 
         loop {
-            let stream = match listener.accept().await {
+            let (stream, client_addr) = match listener.accept().await {
                 Ok((stream, client_addr)) => {
                     debug!("new connection from {client_addr:?}");
-                    stream
+                    (stream, client_addr)
                 }
                 Err(err) => {
                     error!("failed to accept connection: {err:?}");
@@ -104,10 +216,68 @@ impl Hyper {
                 }
             };
 
+            // all clients are allowed when no cidr is specified
+            let allowed = self.allow_cidrs.is_empty()
+                || self
+                    .allow_cidrs
+                    .iter()
+                    .any(|cidr| cidr.contains(client_addr.ip()));
+            if !allowed {
+                debug!("rejecting connection from {client_addr:?}");
+            }
+
             let task = self.task.clone();
             tokio::task::spawn(async move {
-                task.task(stream).await;
+                task.task(stream, allowed).await;
             });
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn large_metrics_response() {
+        let mut buf = String::new();
+        for idx in 0..20000 {
+            buf.push_str(&format!("homerouter_test_metric{{idx=\"{idx}\"}} {idx}\n"));
+        }
+        assert!(buf.len() > 500 * 1024);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body = buf.clone();
+        tokio::task::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let io = hyper_util::rt::TokioIo::new(stream);
+            let svc = service::service_fn(move |_| future::ready(metrics_response(body.clone())));
+            let _ = http1::Builder::new().serve_connection(io, svc).await;
+        });
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let io = hyper_util::rt::TokioIo::new(stream);
+        let (mut sender, conn) = hyper::client::conn::http1::handshake(io).await.unwrap();
+        tokio::task::spawn(conn);
+
+        let req = Request::get("/metrics")
+            .body(http_body_util::Empty::<body::Bytes>::new())
+            .unwrap();
+        let resp = sender.send_request(req).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(
+            resp.headers()[header::CONTENT_LENGTH],
+            buf.len().to_string().as_str()
+        );
+        assert!(!resp.headers().contains_key(header::TRANSFER_ENCODING));
+        assert_eq!(
+            resp.headers()[header::CONTENT_TYPE],
+            collector::Collector::content_type()
+        );
+
+        let received = resp.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(received, buf.as_bytes());
+    }
+}
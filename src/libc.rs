@@ -1,8 +1,8 @@
 // Copyright 2025 Google LLC
 // SPDX-License-Identifier: MIT
 
-use anyhow::{Context, Result};
-use std::{ffi, io, mem, path};
+use anyhow::{Context, Result, anyhow};
+use std::{ffi, fs, io, mem, os::fd::AsRawFd, path};
 
 pub fn sysconf_page_size() -> u64 {
     // SAFETY: valid sysconf call with validation
@@ -40,3 +40,121 @@ pub fn statvfs_size(path: impl AsRef<path::Path>) -> Result<[u64; 3]> {
     let size = [stat.f_blocks, stat.f_bfree, stat.f_bavail].map(|blocks| blocks * stat.f_frsize);
     Ok(size)
 }
+
+pub fn uname() -> Result<[String; 3]> {
+    let mut uts = mem::MaybeUninit::<libc::utsname>::uninit();
+
+    // SAFETY: the pointer is valid
+    let ret = unsafe { libc::uname(uts.as_mut_ptr()) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error()).context("failed to uname");
+    }
+    // SAFETY: ret is 0
+    let uts = unsafe { uts.assume_init() };
+
+    let names = [uts.release, uts.version, uts.machine].map(|name| {
+        // SAFETY: the kernel nul-terminates the fields
+        let name = unsafe { ffi::CStr::from_ptr(name.as_ptr()) };
+        name.to_string_lossy().into_owned()
+    });
+    Ok(names)
+}
+
+pub fn adjtimex_sync() -> Result<(bool, f64)> {
+    // SAFETY: all-zero is a valid timex and modes 0 makes it read-only
+    let mut tx: libc::timex = unsafe { mem::zeroed() };
+
+    // SAFETY: the pointer is valid
+    let ret = unsafe { libc::adjtimex(&mut tx) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error()).context("failed to adjtimex");
+    }
+
+    let synced = ret != libc::TIME_ERROR && tx.status & libc::STA_UNSYNC == 0;
+    let offset = if tx.status & libc::STA_NANO != 0 {
+        tx.offset as f64 / 1e9
+    } else {
+        tx.offset as f64 / 1e6
+    };
+    Ok((synced, offset))
+}
+
+pub fn setns_net(ns: &fs::File) -> Result<()> {
+    // SAFETY: the fd is valid
+    let ret = unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error()).context("failed to setns");
+    }
+
+    Ok(())
+}
+
+pub fn if_nametoindex(name: &str) -> Result<u32> {
+    let c_name = ffi::CString::new(name)?;
+
+    // SAFETY: the pointer is valid
+    let index = unsafe { libc::if_nametoindex(c_name.as_ptr()) };
+    if index == 0 {
+        return Err(io::Error::last_os_error()).context(format!("failed to find {name}"));
+    }
+
+    Ok(index)
+}
+
+// struct nvme_passthru_cmd of linux/nvme_ioctl.h
+#[repr(C)]
+#[derive(Default)]
+struct NvmePassthruCmd {
+    opcode: u8,
+    flags: u8,
+    rsvd1: u16,
+    nsid: u32,
+    cdw2: u32,
+    cdw3: u32,
+    metadata: u64,
+    addr: u64,
+    metadata_len: u32,
+    data_len: u32,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+    timeout_ms: u32,
+    result: u32,
+}
+
+// _IOWR('N', 0x41, struct nvme_passthru_cmd)
+const NVME_IOCTL_ADMIN_CMD: u32 = 0xc0484e41;
+const NVME_ADMIN_GET_LOG_PAGE: u8 = 0x02;
+const NVME_LOG_SMART: u32 = 0x02;
+const NVME_NSID_ALL: u32 = 0xffffffff;
+
+// returns critical warning and percentage used of the smart log
+pub fn nvme_smart_log(dev: impl AsRef<path::Path>) -> Result<(u8, u8)> {
+    let dev = dev.as_ref();
+    let file = fs::File::open(dev).with_context(|| format!("failed to open {dev:?}"))?;
+
+    let mut log = [0u8; 512];
+    let mut cmd = NvmePassthruCmd {
+        opcode: NVME_ADMIN_GET_LOG_PAGE,
+        nsid: NVME_NSID_ALL,
+        addr: log.as_mut_ptr() as u64,
+        data_len: log.len() as u32,
+        // number of dwords minus 1, and log page id
+        cdw10: ((log.len() as u32 / 4 - 1) << 16) | NVME_LOG_SMART,
+        ..Default::default()
+    };
+
+    // SAFETY: the fd is valid and cmd points to a buffer of data_len bytes
+    let ret = unsafe { libc::ioctl(file.as_raw_fd(), NVME_IOCTL_ADMIN_CMD as _, &mut cmd) };
+    if ret < 0 {
+        return Err(io::Error::last_os_error())
+            .context(format!("failed to get smart log of {dev:?}"));
+    } else if ret > 0 {
+        return Err(anyhow!("{dev:?} returned nvme status {ret:#x}"));
+    }
+
+    Ok((log[0], log[5]))
+}
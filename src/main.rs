@@ -7,23 +7,50 @@
 
 mod collector;
 mod config;
+mod http_client;
 mod hyper;
 mod libc;
 mod metric;
 
 use log::{error, info};
+use std::{io::Write, sync};
+use tokio::signal::unix;
 
 fn init_logger() {
     let module = env!("CARGO_CRATE_NAME");
-    let module_filter = if config::get().debug {
-        log::LevelFilter::Debug
-    } else {
-        log::LevelFilter::Info
+    let module_filter = config::get().log_level;
+
+    let mut builder = env_logger::Builder::from_default_env();
+    builder.filter_module(module, module_filter);
+
+    if let config::LogFormat::Json = config::get().log_format {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "timestamp": buf.timestamp().to_string(),
+                "level": record.level().as_str(),
+                "module": record.module_path(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+
+    builder.init();
+}
+
+async fn wait_for_shutdown() {
+    let (Ok(mut sigint), Ok(mut sigterm)) = (
+        unix::signal(unix::SignalKind::interrupt()),
+        unix::signal(unix::SignalKind::terminate()),
+    ) else {
+        error!("failed to install signal handlers");
+        return std::future::pending().await;
     };
 
-    env_logger::Builder::from_default_env()
-        .filter_module(module, module_filter)
-        .init();
+    tokio::select! {
+        _ = sigint.recv() => (),
+        _ = sigterm.recv() => (),
+    }
 }
 
 #[tokio::main]
@@ -34,14 +61,14 @@ async fn main() {
     info!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
 
     let collector = match collector::Collector::new() {
-        Ok(collector) => collector,
+        Ok(collector) => sync::Arc::new(collector),
         Err(err) => {
             error!("failed to initialize collector: {err:?}");
             return;
         }
     };
 
-    let hyper = match hyper::Hyper::new(collector) {
+    let hyper = match hyper::Hyper::new(collector.clone()) {
         Ok(hyper) => hyper,
         Err(err) => {
             error!("failed to initialize hyper: {err:?}");
@@ -49,7 +76,14 @@ async fn main() {
         }
     };
 
-    if let Err(err) = hyper.run().await {
-        error!("failed to run web server: {err:?}");
+    tokio::select! {
+        res = hyper.run() => {
+            if let Err(err) = res {
+                error!("failed to run web server: {err:?}");
+            }
+        }
+        _ = wait_for_shutdown() => info!("shutting down"),
     }
+
+    collector.save_state();
 }
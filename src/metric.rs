@@ -1,31 +1,68 @@
 // Copyright 2025 Google LLC
 // SPDX-License-Identifier: MIT
 
+use anyhow::{Context, Result, anyhow};
 use std::{
+    collections,
     fmt::{self, Write},
-    iter, time,
+    fs, iter, path, sync, time,
 };
 
+// forget the offset of a series that has not been seen for this long
+const COUNTER_OFFSET_TIMEOUT: time::Duration = time::Duration::from_secs(3600);
+
+#[derive(Clone, Copy)]
+pub enum Timestamps {
+    // only for metrics that have their own timestamps
+    Auto,
+    // fall back to the scrape time
+    Always,
+    // let prometheus assign the scrape time
+    Never,
+}
+
+/// Selects the metrics to export by their final names.
+#[derive(Default)]
+pub struct NameFilter {
+    pub include: Option<regex::Regex>,
+    pub exclude: Option<regex::Regex>,
+}
+
+impl NameFilter {
+    fn matches(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|re| re.is_match(name))
+            && !self.exclude.as_ref().is_some_and(|re| re.is_match(name))
+    }
+}
+
 pub enum Unit {
+    BitsPerSecond,
     Bytes,
+    BytesPerSecond,
     Celsius,
+    Decibels,
     Hertz,
-    Info,
     None,
     Packets,
+    Ratio,
     Seconds,
+    Volts,
 }
 
 impl Unit {
     fn as_suffix(&self) -> &'static str {
         match self {
+            Unit::BitsPerSecond => "_bits_per_second",
             Unit::Bytes => "_bytes",
+            Unit::BytesPerSecond => "_bytes_per_second",
             Unit::Celsius => "_celsius",
+            Unit::Decibels => "_decibels",
             Unit::Hertz => "_hertz",
-            Unit::Info => "_info",
             Unit::None => "",
             Unit::Packets => "_packets",
+            Unit::Ratio => "_ratio",
             Unit::Seconds => "_seconds",
+            Unit::Volts => "_volts",
         }
     }
 }
@@ -33,6 +70,8 @@ impl Unit {
 pub enum Type {
     Counter,
     Gauge,
+    // labels carry the information and the value is always 1
+    Info,
 }
 
 impl Type {
@@ -40,6 +79,7 @@ impl Type {
         match self {
             Type::Counter => "_total",
             Type::Gauge => "",
+            Type::Info => "_info",
         }
     }
 
@@ -47,10 +87,164 @@ impl Type {
         match self {
             Type::Counter => "counter",
             Type::Gauge => "gauge",
+            // the text format predates the info type
+            Type::Info => "gauge",
+        }
+    }
+}
+
+pub trait Value: fmt::Display {
+    fn as_f64(&self) -> Option<f64> {
+        None
+    }
+
+    fn to_f64(&self) -> f64;
+}
+
+macro_rules! impl_int_value {
+    ($($ty:ty),*) => {
+        $(impl Value for $ty {
+            fn to_f64(&self) -> f64 {
+                *self as f64
+            }
+        })*
+    };
+}
+
+impl_int_value!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl<T: Value> Value for &T {
+    fn as_f64(&self) -> Option<f64> {
+        (*self).as_f64()
+    }
+
+    fn to_f64(&self) -> f64 {
+        (*self).to_f64()
+    }
+}
+
+impl Value for f64 {
+    fn as_f64(&self) -> Option<f64> {
+        Some(*self)
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+}
+
+struct CounterOffset {
+    last: f64,
+    offset: f64,
+    seen: time::Instant,
+}
+
+/// Keeps counters monotonic when their sources reset (e.g., an interface
+/// is recreated or an nft set is flushed).
+#[derive(Default)]
+pub struct CounterOffsets {
+    // keyed by the metric name and labels
+    series: sync::Mutex<collections::HashMap<String, CounterOffset>>,
+}
+
+impl CounterOffsets {
+    fn apply(&self, key: &str, val: f64) -> f64 {
+        let mut series = self.series.lock().unwrap();
+        let now = time::Instant::now();
+
+        let Some(ent) = series.get_mut(key) else {
+            series.insert(
+                key.to_string(),
+                CounterOffset {
+                    last: val,
+                    offset: 0.0,
+                    seen: now,
+                },
+            );
+            return val;
+        };
+
+        if val < ent.last {
+            ent.offset += ent.last;
         }
+        ent.last = val;
+        ent.seen = now;
+
+        val + ent.offset
+    }
+
+    pub fn load(path: &path::Path) -> Result<Self> {
+        let data = fs::read(path).with_context(|| format!("failed to read {path:?}"))?;
+        let state: serde_json::Value =
+            serde_json::from_slice(&data).with_context(|| format!("failed to parse {path:?}"))?;
+        let state = state
+            .as_object()
+            .ok_or_else(|| anyhow!("unexpected state in {path:?}"))?;
+
+        // each series is saved as [last, offset]
+        let now = time::Instant::now();
+        let series = state
+            .iter()
+            .filter_map(|(key, val)| {
+                let last = val.get(0)?.as_f64()?;
+                let offset = val.get(1)?.as_f64()?;
+                let ent = CounterOffset {
+                    last,
+                    offset,
+                    seen: now,
+                };
+                Some((key.clone(), ent))
+            })
+            .collect();
+
+        Ok(CounterOffsets {
+            series: sync::Mutex::new(series),
+        })
+    }
+
+    pub fn save(&self, path: &path::Path) -> Result<()> {
+        let state: serde_json::Map<_, _> = self
+            .series
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(key, ent)| (key.clone(), serde_json::json!([ent.last, ent.offset])))
+            .collect();
+        let data = serde_json::to_vec(&state)?;
+
+        // write to a temp file first such that a crash does not corrupt the state
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data).with_context(|| format!("failed to write {tmp_path:?}"))?;
+        fs::rename(&tmp_path, path).with_context(|| format!("failed to rename to {path:?}"))?;
+
+        Ok(())
+    }
+
+    pub fn prune(&self) {
+        let now = time::Instant::now();
+        self.series
+            .lock()
+            .unwrap()
+            .retain(|_, ent| now.duration_since(ent.seen) < COUNTER_OFFSET_TIMEOUT);
     }
 }
 
+// format with the given significant digits, without rounding the integer part
+fn format_f64(val: f64, precision: usize) -> String {
+    if !val.is_finite() || val == 0.0 {
+        return val.to_string();
+    }
+
+    let int_digits = val.abs().log10().floor() as i64 + 1;
+    let decimals = (precision as i64 - int_digits).max(0) as usize;
+    let mut s = format!("{val:.decimals$}");
+    if s.contains('.') {
+        s.truncate(s.trim_end_matches('0').trim_end_matches('.').len());
+    }
+
+    s
+}
+
 pub struct Info<const N: usize> {
     pub subsys: &'static str,
     pub name: &'static str,
@@ -64,43 +258,77 @@ pub struct MetricEncoder<'a, const N: usize> {
     writer: &'a mut String,
     name: String,
     label_keys: &'a [&'a str; N],
+    const_labels: &'a [(&'a str, &'a str)],
+    float_precision: Option<usize>,
+    counter_offsets: Option<&'a CounterOffsets>,
     timestamp: i64,
+    // filtered out by the name filter
+    skipped: bool,
 }
 
 impl<'a, const N: usize> MetricEncoder<'a, N> {
+    #[allow(clippy::too_many_arguments)]
     fn new(
         writer: &'a mut String,
         namespace: &str,
+        const_labels: &'a [(&'a str, &'a str)],
+        subsys_prefixes: &[(&str, &str)],
+        float_precision: Option<usize>,
+        counter_offsets: Option<&'a CounterOffsets>,
+        name_filter: &NameFilter,
         info: &'a Info<N>,
         timestamp: Option<time::SystemTime>,
     ) -> Self {
-        let name = format!(
-            "{}_{}_{}{}{}",
-            namespace,
-            info.subsys,
-            info.name,
-            info.unit.as_suffix(),
-            info.ty.as_suffix()
-        );
+        // a prefix replaces both the namespace and the subsystem
+        let prefix = subsys_prefixes
+            .iter()
+            .find(|(subsys, _)| *subsys == info.subsys)
+            .map(|(_, prefix)| *prefix);
+        let parts = match prefix {
+            Some(prefix) => ["", prefix, info.name],
+            None => [namespace, info.subsys, info.name],
+        };
+
+        let mut name = String::new();
+        for part in parts {
+            if part.is_empty() {
+                continue;
+            }
+            if !name.is_empty() {
+                name.push('_');
+            }
+            name.push_str(part);
+        }
+        name.push_str(info.unit.as_suffix());
+        name.push_str(info.ty.as_suffix());
         let label_keys = &info.label_keys;
+        let counter_offsets = counter_offsets.filter(|_| matches!(info.ty, Type::Counter));
         let timestamp = timestamp.map_or(0, |ts| {
             ts.duration_since(time::UNIX_EPOCH)
                 .map_or(0, |dur| dur.as_millis() as i64)
         });
 
+        let skipped = !name_filter.matches(&name);
+
         let mut menc = MetricEncoder {
             writer,
             name,
             label_keys,
+            const_labels,
+            float_precision,
+            counter_offsets,
             timestamp,
+            skipped,
         };
 
-        menc.write_info(info);
+        if !menc.skipped {
+            menc.write_help(info);
+        }
 
         menc
     }
 
-    fn write_info(&mut self, info: &Info<N>) {
+    fn write_help(&mut self, info: &Info<N>) {
         let _ = self
             .writer
             .write_fmt(format_args!("# HELP {} {}\n", self.name, info.help));
@@ -109,40 +337,65 @@ impl<'a, const N: usize> MetricEncoder<'a, N> {
             .write_fmt(format_args!("# TYPE {} {}\n", self.name, info.ty.as_str()));
     }
 
-    fn write_labels(&mut self, label_vals: &[&str; N]) {
-        if N == 0 {
-            return;
+    fn write_label(&mut self, key: &str, val: &str) {
+        let _ = self.writer.write_fmt(format_args!("{}=\"", key));
+        for c in val.chars() {
+            let _ = match c {
+                '\\' => self.writer.write_str(r"\\"),
+                '"' => self.writer.write_str(r#"\""#),
+                '\n' => self.writer.write_str(r"\n"),
+                c => self.writer.write_char(c),
+            };
         }
+        let _ = self.writer.write_char('"');
+    }
 
-        let _ = self.writer.write_char('{');
-
+    fn write_labels(&mut self, label_vals: &[&str; N]) {
+        let label_keys = self.label_keys;
+        let const_labels = self.const_labels;
+        // an empty label value is equivalent to a missing label
+        let labels = iter::zip(label_keys.iter().copied(), label_vals.iter().copied())
+            .chain(const_labels.iter().copied())
+            .filter(|(_, val)| !val.is_empty());
         let mut first = true;
-        for (key, val) in iter::zip(self.label_keys, label_vals) {
-            if first {
-                first = false;
-            } else {
-                let _ = self.writer.write_char(',');
-            }
+        for (key, val) in labels {
+            let sep = if first { '{' } else { ',' };
+            first = false;
+            let _ = self.writer.write_char(sep);
 
-            let _ = self.writer.write_fmt(format_args!("{}=\"", key));
-            for c in val.chars() {
-                let _ = match c {
-                    '\\' => self.writer.write_str(r"\\"),
-                    '"' => self.writer.write_str(r#"\""#),
-                    '\n' => self.writer.write_str(r"\n"),
-                    c => self.writer.write_char(c),
-                };
-            }
-            let _ = self.writer.write_char('"');
+            self.write_label(key, val);
         }
 
-        let _ = self.writer.write_char('}');
+        if !first {
+            let _ = self.writer.write_char('}');
+        }
     }
 
-    pub fn write<T: fmt::Display>(&mut self, label_vals: &[&str; N], val: T) {
+    pub fn write<T: Value>(&mut self, label_vals: &[&str; N], val: T) {
+        if self.skipped {
+            return;
+        }
+
+        let start = self.writer.len();
         let _ = self.writer.write_str(&self.name);
         self.write_labels(label_vals);
 
+        let accumulated = self
+            .counter_offsets
+            .map(|offsets| offsets.apply(&self.writer[start..], val.to_f64()));
+
+        let rounded;
+        let val: &dyn fmt::Display = match (self.float_precision, accumulated.or(val.as_f64())) {
+            (Some(precision), Some(f)) => {
+                rounded = format_f64(f, precision);
+                &rounded
+            }
+            _ => match &accumulated {
+                Some(f) => f,
+                None => &val,
+            },
+        };
+
         let _ = if self.timestamp > 0 {
             self.writer
                 .write_fmt(format_args!(" {} {}\n", val, self.timestamp))
@@ -150,16 +403,47 @@ impl<'a, const N: usize> MetricEncoder<'a, N> {
             self.writer.write_fmt(format_args!(" {}\n", val))
         };
     }
+
+    pub fn write_info(&mut self, label_vals: &[&str; N]) {
+        self.write(label_vals, 1);
+    }
 }
 
 pub struct Encoder<'a> {
     writer: &'a mut String,
     namespace: &'a str,
+    const_labels: &'a [(&'a str, &'a str)],
+    subsys_prefixes: &'a [(&'a str, &'a str)],
+    float_precision: Option<usize>,
+    timestamps: Timestamps,
+    counter_offsets: Option<&'a CounterOffsets>,
+    name_filter: &'a NameFilter,
+    now: time::SystemTime,
 }
 
 impl<'a> Encoder<'a> {
-    pub fn new(writer: &'a mut String, namespace: &'a str) -> Self {
-        Encoder { writer, namespace }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        writer: &'a mut String,
+        namespace: &'a str,
+        const_labels: &'a [(&'a str, &'a str)],
+        subsys_prefixes: &'a [(&'a str, &'a str)],
+        float_precision: Option<usize>,
+        timestamps: Timestamps,
+        counter_offsets: Option<&'a CounterOffsets>,
+        name_filter: &'a NameFilter,
+    ) -> Self {
+        Encoder {
+            writer,
+            namespace,
+            const_labels,
+            subsys_prefixes,
+            float_precision,
+            timestamps,
+            counter_offsets,
+            name_filter,
+            now: time::SystemTime::now(),
+        }
     }
 
     pub fn with_info<'b, const N: usize>(
@@ -167,15 +451,254 @@ impl<'a> Encoder<'a> {
         info: &'b Info<N>,
         timestamp: Option<time::SystemTime>,
     ) -> MetricEncoder<'b, N> {
-        MetricEncoder::new(self.writer, self.namespace, info, timestamp)
+        let timestamp = match self.timestamps {
+            Timestamps::Auto => timestamp,
+            Timestamps::Always => timestamp.or(Some(self.now)),
+            Timestamps::Never => None,
+        };
+
+        MetricEncoder::new(
+            self.writer,
+            self.namespace,
+            self.const_labels,
+            self.subsys_prefixes,
+            self.float_precision,
+            self.counter_offsets,
+            self.name_filter,
+            info,
+            timestamp,
+        )
     }
 
-    pub fn write<T: fmt::Display>(
-        &mut self,
-        info: &Info<0>,
-        val: T,
-        timestamp: Option<time::SystemTime>,
-    ) {
+    pub fn write<T: Value>(&mut self, info: &Info<0>, val: T, timestamp: Option<time::SystemTime>) {
         self.with_info(info, timestamp).write(&[], val);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // parses with a real parser, which skips lines it does not understand
+    fn parse(text: &str) -> Vec<prometheus_parse::Sample> {
+        let lines = text.lines().map(|line| Ok(line.to_string()));
+        let scrape = prometheus_parse::Scrape::parse(lines).unwrap();
+
+        let count = text.lines().filter(|line| !line.starts_with('#')).count();
+        assert_eq!(scrape.samples.len(), count, "{text}");
+
+        scrape.samples
+    }
+
+    fn encode(
+        const_labels: &[(&str, &str)],
+        float_precision: Option<usize>,
+        name_filter: &NameFilter,
+        f: impl FnOnce(&mut Encoder),
+    ) -> String {
+        let mut text = String::new();
+        let mut enc = Encoder::new(
+            &mut text,
+            "router",
+            const_labels,
+            &[("power", "ups")],
+            float_precision,
+            Timestamps::Auto,
+            None,
+            name_filter,
+        );
+        f(&mut enc);
+
+        text
+    }
+
+    const RX: Info<1> = Info {
+        subsys: "network",
+        name: "link_rx",
+        help: "Received bytes.",
+        unit: Unit::Bytes,
+        ty: Type::Counter,
+        label_keys: ["device"],
+    };
+
+    const TEMP: Info<1> = Info {
+        subsys: "hw",
+        name: "temp",
+        help: "Temperature.",
+        unit: Unit::Celsius,
+        ty: Type::Gauge,
+        label_keys: ["sensor"],
+    };
+
+    const HOST: Info<2> = Info {
+        subsys: "host",
+        name: "os",
+        help: "Host information.",
+        unit: Unit::None,
+        ty: Type::Info,
+        label_keys: ["hostname", "release"],
+    };
+
+    const UPS: Info<0> = Info {
+        subsys: "power",
+        name: "up",
+        help: "Whether the UPS is reachable.",
+        unit: Unit::None,
+        ty: Type::Gauge,
+        label_keys: [],
+    };
+
+    #[test]
+    fn const_labels() {
+        let text = encode(
+            &[("instance", "gw"), ("site", "home")],
+            None,
+            &Default::default(),
+            |enc| {
+                let mut menc = enc.with_info(&RX, None);
+                menc.write(&["eth0"], 10u64);
+                // an empty value drops the label
+                menc.write(&[""], 20u64);
+                enc.write(&UPS, 1, None);
+            },
+        );
+        let samples = parse(&text);
+
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].metric, "router_network_link_rx_bytes_total");
+        assert_eq!(samples[0].labels.len(), 3);
+        assert_eq!(samples[0].labels.get("device"), Some("eth0"));
+        assert_eq!(samples[0].labels.get("instance"), Some("gw"));
+        assert_eq!(samples[0].labels.get("site"), Some("home"));
+        assert_eq!(samples[0].value, prometheus_parse::Value::Counter(10.0));
+        assert_eq!(samples[1].labels.len(), 2);
+        assert_eq!(samples[1].labels.get("device"), None);
+        // the prefix replaces the namespace and the subsystem
+        assert_eq!(samples[2].metric, "ups_up");
+        assert_eq!(samples[2].labels.len(), 2);
+        // no timestamp
+        assert!(text.ends_with("ups_up{instance=\"gw\",site=\"home\"} 1\n"));
+    }
+
+    #[test]
+    fn label_escaping() {
+        // values and how they are escaped
+        let vals = [
+            (r"C:\temp", r"C:\\temp"),
+            (r#"say "hi""#, r#"say \"hi\""#),
+            ("two\nlines", r"two\nlines"),
+            (r#"\"\n"#, r#"\\\"\\n"#),
+            ("ünïcode {},=", "ünïcode {},="),
+        ];
+
+        let text = encode(
+            &[("note", "a\\b\"c\nd")],
+            None,
+            &Default::default(),
+            |enc| {
+                let mut menc = enc.with_info(&TEMP, None);
+                for (val, _) in vals {
+                    menc.write(&[val], 1u64);
+                }
+            },
+        );
+
+        // the parser does not unescape label values, so the text is checked
+        let lines: Vec<_> = text.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(lines.len(), vals.len());
+        for (line, (_, escaped)) in iter::zip(lines, vals) {
+            let expected =
+                format!(r#"router_hw_temp_celsius{{sensor="{escaped}",note="a\\b\"c\nd"}} 1"#);
+            assert_eq!(line, expected);
+        }
+    }
+
+    #[test]
+    fn info_type() {
+        let text = encode(&[], None, &Default::default(), |enc| {
+            enc.with_info(&HOST, None).write_info(&["gw", "6.12.0"]);
+        });
+        let samples = parse(&text);
+
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].metric, "router_host_os_info");
+        assert_eq!(samples[0].value, prometheus_parse::Value::Gauge(1.0));
+        assert_eq!(samples[0].labels.get("release"), Some("6.12.0"));
+        assert!(text.ends_with("} 1\n"));
+    }
+
+    #[test]
+    fn float_precision() {
+        // value, precision, and expected text
+        let cases = [
+            (1234.5678, Some(3), "1235"),
+            (0.012345, Some(3), "0.0123"),
+            (-42.126, Some(4), "-42.13"),
+            (2.5, Some(6), "2.5"),
+            (0.0, Some(3), "0"),
+            (1234.5678, None, "1234.5678"),
+        ];
+        for (val, precision, expected) in cases {
+            let text = encode(&[], precision, &Default::default(), |enc| {
+                enc.with_info(&TEMP, None).write(&["cpu"], val);
+            });
+            assert_eq!(parse(&text).len(), 1);
+            assert!(text.ends_with(&format!("}} {expected}\n")), "{text}");
+        }
+
+        // integers are never rounded
+        let text = encode(&[], Some(3), &Default::default(), |enc| {
+            enc.with_info(&RX, None).write(&["eth0"], 123456789u64);
+        });
+        assert!(text.ends_with("} 123456789\n"), "{text}");
+    }
+
+    #[test]
+    fn timestamps() {
+        let ts = time::UNIX_EPOCH + time::Duration::from_millis(1_700_000_000_123);
+        let text = encode(&[], None, &Default::default(), |enc| {
+            enc.with_info(&TEMP, Some(ts)).write(&["cpu"], 40u64);
+        });
+        let samples = parse(&text);
+
+        assert_eq!(samples[0].timestamp.timestamp_millis(), 1_700_000_000_123);
+    }
+
+    #[test]
+    fn name_filters() {
+        let encode_all = |name_filter: &NameFilter| {
+            let text = encode(&[], None, name_filter, |enc| {
+                enc.with_info(&RX, None).write(&["eth0"], 1u64);
+                enc.with_info(&TEMP, None).write(&["cpu"], 40u64);
+                enc.with_info(&HOST, None).write_info(&["gw", "6.12.0"]);
+            });
+            parse(&text)
+                .into_iter()
+                .map(|sample| sample.metric)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(encode_all(&Default::default()).len(), 3);
+
+        let include = NameFilter {
+            include: Some(regex::Regex::new("^router_(network|host)_").unwrap()),
+            exclude: None,
+        };
+        assert_eq!(
+            encode_all(&include),
+            ["router_network_link_rx_bytes_total", "router_host_os_info"]
+        );
+
+        // the filter applies to the final names with the suffixes
+        let exclude = NameFilter {
+            include: None,
+            exclude: Some(regex::Regex::new("_(total|info)$").unwrap()),
+        };
+        assert_eq!(encode_all(&exclude), ["router_hw_temp_celsius"]);
+
+        let both = NameFilter {
+            include: Some(regex::Regex::new("^router_").unwrap()),
+            exclude: Some(regex::Regex::new("temp").unwrap()),
+        };
+        assert_eq!(encode_all(&both).len(), 2);
+    }
+}